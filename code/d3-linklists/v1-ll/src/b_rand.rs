@@ -0,0 +1,32 @@
+// Same generator as d2-sorting/b_rand, without the global and BigGen
+// so the list crate does not need lazy_static or num-bigint.
+#[derive(Debug)]
+pub struct RandGen {
+    curr: usize,
+    mul: usize,
+    inc: usize,
+    modulo: usize,
+}
+
+impl RandGen {
+    pub fn new(curr: usize, mul: usize, inc: usize, modulo: usize) -> Self {
+        RandGen {
+            curr,
+            mul,
+            inc,
+            modulo,
+        }
+    }
+
+    pub fn seeded(curr: usize) -> Self {
+        RandGen::new(curr, 56394237, 346423491, 23254544563)
+    }
+}
+
+impl Iterator for RandGen {
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.curr = (self.curr * self.mul + self.inc) % self.modulo;
+        Some(self.curr)
+    }
+}
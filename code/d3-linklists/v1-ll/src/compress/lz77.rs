@@ -0,0 +1,210 @@
+//! LZ77 with DEFLATE's window and match sizes. Repeats are found through
+//! hash chains on their first 3 bytes, and the literal/length and distance
+//! streams are then coded with canonical Huffman codes.
+use crate::huffman::{BitReader, BitWriter, CanonicalCodec, HuffErr, DEFLATE_MAX_BITS};
+use std::collections::HashMap;
+
+pub const WINDOW: usize = 1 << 15;
+pub const MIN_MATCH: usize = 3;
+pub const MAX_MATCH: usize = 258;
+//earlier positions tried per match, more finds longer matches but slower
+const MAX_CHAIN: usize = 64;
+//literal/length symbols at or above this are lengths
+const LEN_BASE: u16 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Literal(u8),
+    /// Copy len bytes from dist back
+    Match {
+        len: u16,
+        dist: u16,
+    },
+}
+
+fn match_len(data: &[u8], from: usize, at: usize) -> usize {
+    let mut n = 0;
+    while at + n < data.len() && n < MAX_MATCH && data[from + n] == data[at + n] {
+        n += 1;
+    }
+    n
+}
+
+/// Greedy parse, always taking the longest match found at each step
+pub fn tokens(data: &[u8]) -> Vec<Token> {
+    // last position each 3 byte prefix was seen, and before that for each position
+    let mut head: HashMap<&[u8], usize> = HashMap::new();
+    let mut prev: Vec<Option<usize>> = vec![None; data.len()];
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let mut cand = head.get(&data[i..i + MIN_MATCH]).cloned();
+            let mut tries = 0;
+            while let Some(c) = cand {
+                if i - c > WINDOW || tries == MAX_CHAIN {
+                    break;
+                }
+                let l = match_len(data, c, i);
+                if l > best_len {
+                    best_len = l;
+                    best_dist = i - c;
+                    if l == MAX_MATCH {
+                        break;
+                    }
+                }
+                cand = prev[c];
+                tries += 1;
+            }
+        }
+        let step = if best_len >= MIN_MATCH {
+            res.push(Token::Match {
+                len: best_len as u16,
+                dist: best_dist as u16,
+            });
+            best_len
+        } else {
+            res.push(Token::Literal(data[i]));
+            1
+        };
+        for p in i..i + step {
+            if p + MIN_MATCH <= data.len() {
+                prev[p] = head.insert(&data[p..p + MIN_MATCH], p);
+            }
+        }
+        i += step;
+    }
+    res
+}
+
+/// Matches may overlap what they write, so copy a byte at a time
+pub fn expand(tokens: &[Token]) -> Result<Vec<u8>, HuffErr> {
+    let mut res: Vec<u8> = Vec::new();
+    for t in tokens {
+        match *t {
+            Token::Literal(b) => res.push(b),
+            Token::Match { len, dist } => {
+                let dist = dist as usize;
+                if dist == 0 || dist > res.len() {
+                    return Err(HuffErr::BadHeader);
+                }
+                for _ in 0..len {
+                    res.push(res[res.len() - dist]);
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Distances go as the bit length of dist-1, then the bits below its top one
+fn dist_code(dist: u16) -> (u8, u64, u8) {
+    let d = dist as u64 - 1;
+    let code = (64 - d.leading_zeros()) as u8;
+    let extra = code.saturating_sub(1);
+    (code, d & ((1 << extra) - 1), extra)
+}
+
+fn lit_len_symbol(t: &Token) -> u16 {
+    match t {
+        Token::Literal(b) => *b as u16,
+        Token::Match { len, .. } => LEN_BASE + len - MIN_MATCH as u16,
+    }
+}
+
+/// Token count, the literal/length code, the distance code, then the tokens
+pub fn encode(data: &[u8]) -> Result<Vec<u8>, HuffErr> {
+    let toks = tokens(data);
+    let dists: Vec<u8> = toks
+        .iter()
+        .filter_map(|t| match t {
+            Token::Match { dist, .. } => Some(dist_code(*dist).0),
+            _ => None,
+        })
+        .collect();
+    // 512 literal/length symbols and 16 distance codes both fit in 15 bits
+    let lcodec = CanonicalCodec::new(toks.iter().map(lit_len_symbol), DEFLATE_MAX_BITS).unwrap();
+    let dcodec = CanonicalCodec::new(dists, DEFLATE_MAX_BITS).unwrap();
+
+    let mut w = BitWriter::new();
+    w.push_bits(toks.len() as u64, 64);
+    lcodec.write_header(&mut w);
+    dcodec.write_header(&mut w);
+    for t in &toks {
+        lcodec.write_symbol(&mut w, lit_len_symbol(t))?;
+        if let Token::Match { dist, .. } = t {
+            let (code, bits, extra) = dist_code(*dist);
+            dcodec.write_symbol(&mut w, code)?;
+            w.push_bits(bits, extra);
+        }
+    }
+    Ok(w.finish())
+}
+
+pub fn decode(buf: &[u8]) -> Result<Vec<u8>, HuffErr> {
+    let mut r = BitReader::new(buf);
+    let n = r.read_bits(64).ok_or(HuffErr::Truncated)?;
+    let ldec = CanonicalCodec::<u16>::read_header(&mut r)?.decoder();
+    let ddec = CanonicalCodec::<u8>::read_header(&mut r)?.decoder();
+    let mut toks = Vec::new();
+    for _ in 0..n {
+        let s = ldec.read_symbol(&mut r)?;
+        if s < LEN_BASE {
+            toks.push(Token::Literal(s as u8));
+            continue;
+        }
+        let code = ddec.read_symbol(&mut r)?;
+        if code > 16 {
+            return Err(HuffErr::BadHeader);
+        }
+        let extra = code.saturating_sub(1);
+        let low = r.read_bits(extra).ok_or(HuffErr::Truncated)?;
+        let d = if code == 0 { 0 } else { (1 << extra) | low };
+        toks.push(Token::Match {
+            len: s - LEN_BASE + MIN_MATCH as u16,
+            dist: (d + 1) as u16,
+        });
+    }
+    expand(&toks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_expand() {
+        let data = b"abcabcabcabcabcX abcabc";
+        let toks = tokens(data);
+        assert_eq!(toks[3], Token::Match { len: 12, dist: 3 });
+        assert!(toks.len() < data.len() / 2);
+        assert_eq!(expand(&toks).unwrap(), data.to_vec());
+
+        assert_eq!(dist_code(1), (0, 0, 0));
+        assert_eq!(dist_code(2), (1, 0, 0));
+        assert_eq!(dist_code(6), (3, 1, 2));
+        assert!(expand(&[Token::Match { len: 3, dist: 1 }]).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut log = String::new();
+        for i in 0..3000 {
+            log.push_str(&format!(
+                "12:00:{:02} GET /index.html 200 {}\n",
+                i % 60,
+                i % 7
+            ));
+        }
+        let enc = encode(log.as_bytes()).unwrap();
+        assert!(enc.len() < log.len() / 8, "{} of {}", enc.len(), log.len());
+        assert_eq!(decode(&enc).unwrap(), log.as_bytes());
+
+        let far: Vec<u8> = (0..WINDOW * 2).map(|i| (i * 7 % 251) as u8).collect();
+        assert_eq!(decode(&encode(&far).unwrap()).unwrap(), far);
+        for data in &[&b""[..], b"a", b"aaaaaaaaaaaaaaaaaaaaaaa"] {
+            assert_eq!(decode(&encode(data).unwrap()).unwrap(), data.to_vec());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Whole buffer compressors built on the huffman module, and one without it,
+//! behind one Method enum so they can be compared on the same input.
+use crate::huffman::{self, HuffErr};
+
+pub mod lz77;
+pub mod range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    /// Block by block canonical Huffman
+    Huffman,
+    /// Adaptive range coding of each byte
+    Range,
+    /// LZ77 matches, then Huffman codes for the matches and literals
+    Lz77,
+}
+
+impl Method {
+    pub const ALL: [Method; 3] = [Method::Huffman, Method::Range, Method::Lz77];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Huffman => "huffman",
+            Method::Range => "range",
+            Method::Lz77 => "lz77",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Method> {
+        Method::ALL.iter().cloned().find(|m| m.name() == s)
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, HuffErr> {
+        match self {
+            Method::Huffman => {
+                let mut res = Vec::new();
+                huffman::compress(data, &mut res)?;
+                Ok(res)
+            }
+            Method::Range => Ok(range::encode(data)),
+            Method::Lz77 => lz77::encode(data),
+        }
+    }
+
+    pub fn decompress(self, buf: &[u8]) -> Result<Vec<u8>, HuffErr> {
+        match self {
+            Method::Huffman => {
+                let mut res = Vec::new();
+                huffman::decompress(buf, &mut res)?;
+                Ok(res)
+            }
+            Method::Range => range::decode(buf),
+            Method::Lz77 => lz77::decode(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_methods() {
+        let text = include_bytes!("mod.rs");
+        for m in &Method::ALL {
+            assert_eq!(Method::from_name(m.name()), Some(*m));
+            let enc = m.compress(text).unwrap();
+            assert!(enc.len() < text.len(), "{}", m.name());
+            assert_eq!(m.decompress(&enc).unwrap(), text.to_vec());
+            assert_eq!(m.decompress(&m.compress(b"").unwrap()).unwrap(), b"");
+        }
+        assert_eq!(Method::from_name("zip"), None);
+    }
+}
@@ -0,0 +1,190 @@
+//! Adaptive binary range coder, in the style of LZMA's.
+//! Each byte is coded as 8 bits down a tree of 255 probabilities,
+//! so the model learns which bytes follow which leading bits as it goes.
+use crate::huffman::HuffErr;
+
+const PROB_BITS: u32 = 11;
+const PROB_ONE: u16 = 1 << PROB_BITS;
+//how fast probabilities move towards what was just seen
+const ADAPT: u16 = 5;
+const TOP: u32 = 1 << 24;
+
+struct Encoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    out: Vec<u8>,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Encoder {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            out: Vec::new(),
+        }
+    }
+
+    /// Bytes are held back while they are all 0xFF, in case a carry still has to go into them
+    fn shift_low(&mut self) {
+        if self.low < 0xFF00_0000 || self.low > u32::MAX as u64 {
+            let carry = (self.low >> 32) as u8;
+            let mut temp = self.cache;
+            loop {
+                self.out.push(temp.wrapping_add(carry));
+                temp = 0xFF;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    /// prob is the chance (out of PROB_ONE) of a 0
+    fn encode_bit(&mut self, prob: &mut u16, bit: bool) {
+        let bound = (self.range >> PROB_BITS) * *prob as u32;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *prob -= *prob >> ADAPT;
+        } else {
+            self.range = bound;
+            *prob += (PROB_ONE - *prob) >> ADAPT;
+        }
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        self.out
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    range: u32,
+    code: u32,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buf: &'a [u8]) -> Result<Self, HuffErr> {
+        let mut res = Decoder {
+            buf,
+            pos: 0,
+            range: u32::MAX,
+            code: 0,
+        };
+        for _ in 0..5 {
+            res.code = (res.code << 8) | res.next_byte()? as u32;
+        }
+        Ok(res)
+    }
+
+    fn next_byte(&mut self) -> Result<u8, HuffErr> {
+        let b = *self.buf.get(self.pos).ok_or(HuffErr::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn decode_bit(&mut self, prob: &mut u16) -> Result<bool, HuffErr> {
+        let bound = (self.range >> PROB_BITS) * *prob as u32;
+        let bit = if self.code < bound {
+            self.range = bound;
+            *prob += (PROB_ONE - *prob) >> ADAPT;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            *prob -= *prob >> ADAPT;
+            true
+        };
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next_byte()? as u32;
+        }
+        Ok(bit)
+    }
+}
+
+/// The byte count (8 bytes, big endian), then the coded bits
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut probs = [PROB_ONE / 2; 256];
+    let mut enc = Encoder::new();
+    for b in data {
+        let mut m = 1;
+        for i in (0..8).rev() {
+            let bit = (b >> i) & 1 == 1;
+            enc.encode_bit(&mut probs[m], bit);
+            m = (m << 1) | bit as usize;
+        }
+    }
+    let mut res = (data.len() as u64).to_be_bytes().to_vec();
+    res.extend(enc.finish());
+    res
+}
+
+pub fn decode(buf: &[u8]) -> Result<Vec<u8>, HuffErr> {
+    if buf.len() < 8 {
+        return Err(HuffErr::Truncated);
+    }
+    let mut nb = [0; 8];
+    nb.copy_from_slice(&buf[..8]);
+    let n = u64::from_be_bytes(nb);
+    let mut probs = [PROB_ONE / 2; 256];
+    let mut dec = Decoder::new(&buf[8..])?;
+    let mut res = Vec::new();
+    for _ in 0..n {
+        let mut m = 1;
+        for _ in 0..8 {
+            m = (m << 1) | dec.decode_bit(&mut probs[m])? as usize;
+        }
+        res.push(m as u8);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for data in &[
+            Vec::new(),
+            vec![0u8],
+            vec![255u8; 3000],
+            (0..=255u8).cycle().take(5000).collect::<Vec<u8>>(),
+            b"the rain in spain falls mainly on the plain".to_vec(),
+        ] {
+            assert_eq!(&decode(&encode(data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_adapts_to_skew() {
+        // Mostly one byte, the model should get that down to well under a bit each
+        let data: Vec<u8> = (0..20000u32)
+            .map(|i| if i % 50 == 0 { b'x' } else { b'a' })
+            .collect();
+        let enc = encode(&data);
+        assert!(enc.len() < data.len() / 10, "{}", enc.len());
+        assert_eq!(decode(&enc).unwrap(), data);
+        assert!(matches!(
+            decode(&enc[..enc.len() / 2]),
+            Err(HuffErr::Truncated)
+        ));
+    }
+}
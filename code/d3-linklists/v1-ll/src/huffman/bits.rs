@@ -0,0 +1,117 @@
+/// Packs bits into bytes, the first bit goes in the top of the first byte
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    nbits: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        BitWriter::default()
+    }
+
+    pub fn push(&mut self, b: bool) {
+        if self.nbits.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if b {
+            //pushed a byte above if needed
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.nbits % 8);
+        }
+        self.nbits += 1;
+    }
+
+    /// The low len bits of code, highest first
+    pub fn push_bits(&mut self, code: u64, len: u8) {
+        for i in (0..len).rev() {
+            self.push((code >> i) & 1 == 1);
+        }
+    }
+
+    /// Unused bits in the last byte are left as zero
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits back in the order BitWriter wrote them
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        let b = *self.bytes.get(self.pos / 8)?;
+        let res = b & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Some(res)
+    }
+
+    /// The next len (up to 56) bits without moving on, zeros past the end
+    pub fn peek_bits(&self, len: u8) -> u64 {
+        if len == 0 {
+            return 0;
+        }
+        let start = self.pos / 8;
+        let mut acc = 0u64;
+        for i in 0..8 {
+            acc = (acc << 8) | *self.bytes.get(start + i).unwrap_or(&0) as u64;
+        }
+        (acc << (self.pos % 8)) >> (64 - len as u32)
+    }
+
+    /// Move on len bits, None if that is past the end
+    pub fn skip(&mut self, len: u8) -> Option<()> {
+        if self.pos + len as usize > self.bytes.len() * 8 {
+            return None;
+        }
+        self.pos += len as usize;
+        Some(())
+    }
+
+    /// Bits left before the end
+    pub fn remaining(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.pos)
+    }
+
+    pub fn read_bits(&mut self, len: u8) -> Option<u64> {
+        let mut res = 0;
+        for _ in 0..len {
+            res = (res << 1) | self.read_bit()? as u64;
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_round_trip() {
+        let mut w = BitWriter::new();
+        w.push(true);
+        w.push_bits(0b0110, 4);
+        w.push_bits(0xABCD, 16);
+        let bytes = w.finish();
+        assert_eq!(bytes.len(), 3);
+        assert_eq!(bytes[0], 0b1011_0101);
+
+        let mut r = BitReader::new(&bytes);
+        assert_eq!(r.read_bit(), Some(true));
+        assert_eq!(r.read_bits(4), Some(0b0110));
+        assert_eq!(r.peek_bits(4), 0xA);
+        assert_eq!(r.peek_bits(16), 0xABCD);
+        assert_eq!(r.read_bits(16), Some(0xABCD));
+        assert_eq!(r.peek_bits(8), 0);
+        assert_eq!(r.skip(4), None);
+        assert_eq!(r.read_bits(3), Some(0));
+        assert_eq!(r.read_bit(), None);
+    }
+}
@@ -0,0 +1,353 @@
+//! Canonical codes depend only on each symbol's code length,
+//! so a header need only carry lengths, and any two coders agree on the bits.
+//! Codes are given out the DEFLATE way (RFC 1951 3.2.2), but only that part follows it:
+//! bits are packed highest first and the header is our own list of lengths,
+//! so the output is not a DEFLATE stream.
+use super::{BitReader, BitWriter, HuffErr, HuffNode, Symbol};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Longest code DEFLATE allows
+pub const DEFLATE_MAX_BITS: u8 = 15;
+/// Decode tables have 2^max_len entries, this keeps them at 64K
+pub const MAX_TABLE_BITS: u8 = 16;
+
+/// Depth of every leaf in the tree
+pub fn code_lengths<S: Ord + Copy>(tree: &HuffNode<S>) -> BTreeMap<S, u8> {
+    fn walk<S: Ord + Copy>(t: &HuffNode<S>, depth: u8, res: &mut BTreeMap<S, u8>) {
+        match t {
+            HuffNode::Tree(l, r) => {
+                walk(l, depth + 1, res);
+                walk(r, depth + 1, res);
+            }
+            HuffNode::Leaf(c) => {
+                res.insert(*c, depth);
+            }
+        }
+    }
+    let mut res = BTreeMap::new();
+    walk(tree, 0, &mut res);
+    res
+}
+
+/// Shorter codes first, and in symbol order within a length,
+/// each code one more than the last, shifted up when the length grows
+pub fn canonical_codes<S: Ord + Copy>(lengths: &BTreeMap<S, u8>) -> BTreeMap<S, (u64, u8)> {
+    let mut by_len: Vec<(u8, S)> = lengths
+        .iter()
+        .filter(|(_, l)| **l > 0)
+        .map(|(s, l)| (*l, *s))
+        .collect();
+    by_len.sort();
+    let mut res = BTreeMap::new();
+    let mut code = 0u64;
+    let mut prev = 0;
+    for (l, s) in by_len {
+        code <<= l - prev;
+        res.insert(s, (code, l));
+        code += 1;
+        prev = l;
+    }
+    res
+}
+
+/// Whether lengths make a prefix code, i.e. the Kraft sum is at most 1
+fn fits<S>(lengths: &BTreeMap<S, u8>, max_len: u8) -> bool {
+    let room: u64 = lengths
+        .values()
+        .map(|l| 1u64 << (max_len - std::cmp::min(*l, max_len)))
+        .sum();
+    room <= 1 << max_len && lengths.values().all(|l| *l > 0 && *l <= max_len)
+}
+
+enum Item {
+    Leaf(usize),
+    Pack(Rc<Item>, Rc<Item>),
+}
+
+fn count_leaves(it: &Item, lens: &mut [u8]) {
+    match it {
+        Item::Leaf(i) => lens[*i] += 1,
+        Item::Pack(a, b) => {
+            count_leaves(a, lens);
+            count_leaves(b, lens);
+        }
+    }
+}
+
+/// Optimal code lengths with none longer than max_len, by package-merge.
+/// Each round pairs up the cheapest items of the last list and merges the pairs
+/// back in with the leaves. The first 2n-2 items of the last list then say how
+/// long each code is: one bit for each time its symbol appears in them.
+/// None if max_len bits can't give every symbol its own code.
+pub fn limited_lengths<S: Ord + Copy>(
+    counts: &BTreeMap<S, u64>,
+    max_len: u8,
+) -> Option<BTreeMap<S, u8>> {
+    let n = counts.len();
+    if n == 0 {
+        return Some(BTreeMap::new());
+    }
+    if max_len == 0 || max_len < 64 && n as u64 > 1 << max_len {
+        return None;
+    }
+    if n == 1 {
+        return counts.keys().map(|k| Some((*k, 1))).collect();
+    }
+    let mut syms: Vec<(u64, S)> = counts.iter().map(|(s, c)| (*c, *s)).collect();
+    syms.sort();
+    let leaves: Vec<(u64, Rc<Item>)> = syms
+        .iter()
+        .enumerate()
+        .map(|(i, (c, _))| (*c, Rc::new(Item::Leaf(i))))
+        .collect();
+
+    let mut list = leaves.clone();
+    for _ in 1..max_len {
+        let packs: Vec<(u64, Rc<Item>)> = list
+            .chunks_exact(2)
+            .map(|p| {
+                (
+                    p[0].0 + p[1].0,
+                    Rc::new(Item::Pack(p[0].1.clone(), p[1].1.clone())),
+                )
+            })
+            .collect();
+        // merge, leaves first on a tie
+        let mut merged = Vec::with_capacity(leaves.len() + packs.len());
+        let (mut li, mut pi) = (leaves.iter().peekable(), packs.into_iter().peekable());
+        loop {
+            let take_leaf = match (li.peek(), pi.peek()) {
+                (Some(l), Some(p)) => l.0 <= p.0,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            if take_leaf {
+                merged.extend(li.next().cloned());
+            } else {
+                merged.extend(pi.next());
+            }
+        }
+        list = merged;
+    }
+
+    let mut lens = vec![0u8; n];
+    for (_, it) in list.iter().take(2 * n - 2) {
+        count_leaves(it, &mut lens);
+    }
+    Some(syms.iter().zip(lens).map(|((_, s), l)| (*s, l)).collect())
+}
+
+/// Huffman codec that writes only code lengths in its header,
+/// and decodes with a table lookup per symbol instead of a tree walk.
+#[derive(Debug)]
+pub struct CanonicalCodec<S> {
+    lengths: BTreeMap<S, u8>,
+    codes: BTreeMap<S, (u64, u8)>,
+}
+
+impl<S: Symbol> CanonicalCodec<S> {
+    /// None if max_len is too short for the number of different symbols,
+    /// or longer than the decode table allows
+    pub fn new<I: IntoIterator<Item = S>>(syms: I, max_len: u8) -> Option<Self> {
+        if max_len > MAX_TABLE_BITS {
+            return None;
+        }
+        let mut counts = BTreeMap::new();
+        for c in syms {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        Some(Self::from_lengths(limited_lengths(&counts, max_len)?))
+    }
+
+    pub fn from_lengths(lengths: BTreeMap<S, u8>) -> Self {
+        let codes = canonical_codes(&lengths);
+        CanonicalCodec { lengths, codes }
+    }
+
+    pub fn lengths(&self) -> &BTreeMap<S, u8> {
+        &self.lengths
+    }
+
+    pub fn code(&self, c: S) -> Option<(u64, u8)> {
+        self.codes.get(&c).cloned()
+    }
+
+    /// How many lengths follow, then each symbol and its length in 5 bits.
+    /// Not DEFLATE's run-length coded lengths
+    pub fn write_header(&self, w: &mut BitWriter) {
+        w.push_bits(self.lengths.len() as u64, 32);
+        for (s, l) in &self.lengths {
+            w.push_bits(s.to_bits(), S::BITS);
+            w.push_bits(*l as u64, 5);
+        }
+    }
+
+    pub fn read_header(r: &mut BitReader) -> Result<Self, HuffErr> {
+        let nlens = r.read_bits(32).ok_or(HuffErr::Truncated)?;
+        let mut lengths = BTreeMap::new();
+        for _ in 0..nlens {
+            let s = r.read_bits(S::BITS).ok_or(HuffErr::Truncated)?;
+            let s = S::from_bits(s).ok_or(HuffErr::BadHeader)?;
+            let l = r.read_bits(5).ok_or(HuffErr::Truncated)?;
+            lengths.insert(s, l as u8);
+        }
+        let max_len = lengths.values().cloned().max().unwrap_or(0);
+        if max_len > MAX_TABLE_BITS || !fits(&lengths, max_len) {
+            return Err(HuffErr::BadHeader);
+        }
+        Ok(Self::from_lengths(lengths))
+    }
+
+    pub fn write_symbol(&self, w: &mut BitWriter, c: S) -> Result<(), HuffErr> {
+        let (code, len) = self
+            .code(c)
+            .ok_or_else(|| HuffErr::UnknownSymbol(c.to_bits()))?;
+        w.push_bits(code, len);
+        Ok(())
+    }
+
+    /// Every max_len bit pattern that starts with a code maps to that code's symbol
+    pub fn decoder(&self) -> TableDecoder<S> {
+        let max_len = self.lengths.values().cloned().max().unwrap_or(0);
+        let mut table: Vec<Option<(S, u8)>> = vec![None; 1 << max_len];
+        for (s, (code, l)) in &self.codes {
+            let lo = (code << (max_len - l)) as usize;
+            let hi = ((code + 1) << (max_len - l)) as usize;
+            for e in &mut table[lo..hi] {
+                *e = Some((*s, *l));
+            }
+        }
+        TableDecoder { table, max_len }
+    }
+
+    /// Symbol count, the header, then the codes
+    pub fn encode(&self, syms: &[S]) -> Result<Vec<u8>, HuffErr> {
+        let mut w = BitWriter::new();
+        w.push_bits(syms.len() as u64, 64);
+        self.write_header(&mut w);
+        for c in syms {
+            self.write_symbol(&mut w, *c)?;
+        }
+        Ok(w.finish())
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Vec<S>, HuffErr> {
+        let mut r = BitReader::new(buf);
+        let n = r.read_bits(64).ok_or(HuffErr::Truncated)?;
+        let dec = Self::read_header(&mut r)?.decoder();
+        let mut res = Vec::new();
+        for _ in 0..n {
+            res.push(dec.read_symbol(&mut r)?);
+        }
+        Ok(res)
+    }
+}
+
+/// Lookup table from the next max_len bits to the symbol whose code starts them
+#[derive(Debug)]
+pub struct TableDecoder<S> {
+    table: Vec<Option<(S, u8)>>,
+    max_len: u8,
+}
+
+impl<S: Copy> TableDecoder<S> {
+    pub fn read_symbol(&self, r: &mut BitReader) -> Result<S, HuffErr> {
+        let (s, l) = self.table[r.peek_bits(self.max_len) as usize].ok_or(HuffErr::BadHeader)?;
+        r.skip(l).ok_or(HuffErr::Truncated)?;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::huffman::tree_from_counts;
+
+    fn cost(counts: &BTreeMap<u8, u64>, lens: &BTreeMap<u8, u8>) -> u64 {
+        counts.iter().map(|(s, c)| c * lens[s] as u64).sum()
+    }
+
+    #[test]
+    fn test_rfc1951_example() {
+        let lengths: BTreeMap<char, u8> = "ABCDEFGH"
+            .chars()
+            .zip(vec![3, 3, 3, 3, 3, 2, 4, 4])
+            .collect();
+        let codes = canonical_codes(&lengths);
+        let want = [
+            ('A', 0b010),
+            ('B', 0b011),
+            ('C', 0b100),
+            ('D', 0b101),
+            ('E', 0b110),
+            ('F', 0b00),
+            ('G', 0b1110),
+            ('H', 0b1111),
+        ];
+        for (c, code) in &want {
+            assert_eq!(codes[c], (*code, lengths[c]));
+        }
+    }
+
+    #[test]
+    fn test_package_merge() {
+        // Fibonacci counts give the deepest possible tree
+        let mut fib = vec![1u64, 1];
+        while fib.len() < 20 {
+            fib.push(fib[fib.len() - 1] + fib[fib.len() - 2]);
+        }
+        let counts: BTreeMap<u8, u64> =
+            fib.iter().enumerate().map(|(i, c)| (i as u8, *c)).collect();
+
+        // With room to spare it matches the plain Huffman tree
+        let free = limited_lengths(&counts, 32).unwrap();
+        let tree = code_lengths(&tree_from_counts(&counts).unwrap());
+        assert_eq!(cost(&counts, &free), cost(&counts, &tree));
+        assert_eq!(*free.values().max().unwrap(), 19);
+
+        for max in 5..12 {
+            let lens = limited_lengths(&counts, max).unwrap();
+            assert!(lens.values().all(|l| *l <= max));
+            assert!(fits(&lens, max));
+            // a full code, no leftover room
+            let room: u64 = lens.values().map(|l| 1 << (max - l)).sum();
+            assert_eq!(room, 1 << max);
+            assert!(cost(&counts, &lens) >= cost(&counts, &free));
+        }
+        assert!(limited_lengths(&counts, 4).is_none());
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let text = "this is the text we want to squash, and it is mostly lower case";
+        let codec = CanonicalCodec::new(text.bytes(), DEFLATE_MAX_BITS).unwrap();
+        let data = text.as_bytes();
+        let enc = codec.encode(data).unwrap();
+        assert_eq!(CanonicalCodec::<u8>::decode(&enc).unwrap(), data);
+
+        let short = CanonicalCodec::new(text.bytes(), 5).unwrap();
+        assert!(short.lengths().values().all(|l| *l <= 5));
+        let enc = short.encode(data).unwrap();
+        assert_eq!(CanonicalCodec::<u8>::decode(&enc).unwrap(), data);
+
+        let one = CanonicalCodec::new(vec![b'x'; 10], 8).unwrap();
+        assert_eq!(one.code(b'x'), Some((0, 1)));
+        let enc = one.encode(&[b'x'; 10]).unwrap();
+        assert_eq!(CanonicalCodec::<u8>::decode(&enc).unwrap(), vec![b'x'; 10]);
+
+        let empty = CanonicalCodec::<u8>::new(vec![], 8).unwrap();
+        assert_eq!(
+            CanonicalCodec::<u8>::decode(&empty.encode(&[]).unwrap()).unwrap(),
+            vec![]
+        );
+
+        assert!(CanonicalCodec::new(0..=255u8, 7).is_none());
+        assert!(CanonicalCodec::new(text.bytes(), MAX_TABLE_BITS + 1).is_none());
+        assert!(matches!(
+            CanonicalCodec::<u8>::decode(&enc[..enc.len() - 1]),
+            Err(HuffErr::Truncated)
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+use super::{build_tree, BitReader, BitWriter, HuffErr, HuffNode, Symbol};
+use std::collections::BTreeMap;
+
+//A corrupt header could otherwise recurse as deep as it has bits
+const MAX_DEPTH: usize = 64;
+
+/// Codes are found once when the codec is built,
+/// so encoding is a table lookup per symbol rather than a tree search.
+#[derive(Debug)]
+pub struct HuffmanCodec<S> {
+    // None when built from nothing, that can only encode nothing
+    tree: Option<HuffNode<S>>,
+    // code bits, and how many of them are used
+    table: BTreeMap<S, (u64, u8)>,
+}
+
+fn fill_table<S: Ord + Copy>(
+    t: &HuffNode<S>,
+    code: u64,
+    len: u8,
+    table: &mut BTreeMap<S, (u64, u8)>,
+) {
+    match t {
+        HuffNode::Tree(l, r) => {
+            fill_table(l, code << 1, len + 1, table);
+            fill_table(r, (code << 1) | 1, len + 1, table);
+        }
+        HuffNode::Leaf(c) => {
+            table.insert(*c, (code, len));
+        }
+    }
+}
+
+/// Pre order, 0 for a branch, 1 then the symbol for a leaf
+fn write_tree<S: Symbol>(t: &HuffNode<S>, w: &mut BitWriter) {
+    match t {
+        HuffNode::Tree(l, r) => {
+            w.push(false);
+            write_tree(l, w);
+            write_tree(r, w);
+        }
+        HuffNode::Leaf(c) => {
+            w.push(true);
+            w.push_bits(c.to_bits(), S::BITS);
+        }
+    }
+}
+
+fn read_tree<S: Symbol>(r: &mut BitReader, depth: usize) -> Result<HuffNode<S>, HuffErr> {
+    if depth > MAX_DEPTH {
+        return Err(HuffErr::BadHeader);
+    }
+    match r.read_bit().ok_or(HuffErr::Truncated)? {
+        false => {
+            let left = read_tree(r, depth + 1)?;
+            let right = read_tree(r, depth + 1)?;
+            Ok(HuffNode::Tree(Box::new(left), Box::new(right)))
+        }
+        //the encoder never writes a lone leaf, it would give symbols with no bits
+        true if depth == 0 => Err(HuffErr::BadHeader),
+        true => {
+            let n = r.read_bits(S::BITS).ok_or(HuffErr::Truncated)?;
+            let c = S::from_bits(n).ok_or(HuffErr::BadHeader)?;
+            Ok(HuffNode::Leaf(c))
+        }
+    }
+}
+
+impl<S: Symbol> HuffmanCodec<S> {
+    pub fn new<I: IntoIterator<Item = S>>(syms: I) -> Self {
+        Self::from_tree(build_tree(syms))
+    }
+
+    pub fn from_tree(tree: Option<HuffNode<S>>) -> Self {
+        let mut table = BTreeMap::new();
+        if let Some(t) = &tree {
+            fill_table(t, 0, 0, &mut table);
+        }
+        HuffmanCodec { tree, table }
+    }
+
+    pub fn tree(&self) -> Option<&HuffNode<S>> {
+        self.tree.as_ref()
+    }
+
+    pub fn code(&self, c: S) -> Option<(u64, u8)> {
+        self.table.get(&c).cloned()
+    }
+
+    /// The symbol count and tree go first, so the buffer can be decoded on its own
+    pub fn encode(&self, syms: &[S]) -> Result<Vec<u8>, HuffErr> {
+        let mut w = BitWriter::new();
+        w.push_bits(syms.len() as u64, 64);
+        if syms.is_empty() {
+            return Ok(w.finish());
+        }
+        if let Some(t) = &self.tree {
+            write_tree(t, &mut w);
+        }
+        for c in syms {
+            let (code, len) = self
+                .code(*c)
+                .ok_or_else(|| HuffErr::UnknownSymbol(c.to_bits()))?;
+            w.push_bits(code, len);
+        }
+        Ok(w.finish())
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Vec<S>, HuffErr> {
+        let mut r = BitReader::new(buf);
+        let n = r.read_bits(64).ok_or(HuffErr::Truncated)?;
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let tree = read_tree(&mut r, 0)?;
+        // every symbol takes at least a bit, so a bigger count cannot be honest
+        if n > r.remaining() as u64 {
+            return Err(HuffErr::Truncated);
+        }
+        let mut res = Vec::with_capacity(n as usize);
+        for _ in 0..n {
+            let mut t = &tree;
+            while let HuffNode::Tree(left, right) = t {
+                t = match r.read_bit().ok_or(HuffErr::Truncated)? {
+                    false => left,
+                    true => right,
+                };
+            }
+            if let HuffNode::Leaf(c) = t {
+                res.push(*c);
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl HuffmanCodec<char> {
+    pub fn encode_str(&self, s: &str) -> Result<Vec<u8>, HuffErr> {
+        self.encode(&s.chars().collect::<Vec<char>>())
+    }
+
+    pub fn decode_str(buf: &[u8]) -> Result<String, HuffErr> {
+        Ok(Self::decode(buf)?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let s = "at an apple app, and a pear";
+        let codec = HuffmanCodec::new(s.chars());
+        let enc = codec.encode_str(s).unwrap();
+        assert_eq!(HuffmanCodec::decode_str(&enc).unwrap(), s);
+
+        // 'a' is the most common so gets the shortest code
+        let (_, alen) = codec.code('a').unwrap();
+        assert!(codec.table.values().all(|(_, l)| *l >= alen));
+
+        let long = "ab".repeat(500) + "ccccd";
+        let enc = HuffmanCodec::new(long.chars()).encode_str(&long).unwrap();
+        assert!(enc.len() < long.len() / 4);
+        assert_eq!(HuffmanCodec::decode_str(&enc).unwrap(), long);
+    }
+
+    #[test]
+    fn test_empty_single_and_unicode() {
+        let enc = HuffmanCodec::new("".chars()).encode_str("").unwrap();
+        assert_eq!(HuffmanCodec::decode_str(&enc).unwrap(), "");
+
+        // one bit each rather than no bits at all
+        let codec = HuffmanCodec::new("zzzz".chars());
+        assert_eq!(codec.code('z').map(|(_, l)| l), Some(1));
+        let enc = codec.encode_str("zzzz").unwrap();
+        assert_eq!(HuffmanCodec::decode_str(&enc).unwrap(), "zzzz");
+
+        let s = "naïve café ☕";
+        let enc = HuffmanCodec::new(s.chars()).encode_str(s).unwrap();
+        assert_eq!(HuffmanCodec::decode_str(&enc).unwrap(), s);
+    }
+
+    #[test]
+    fn test_bytes_and_errors() {
+        let data: Vec<u8> = (0..=255).chain(0..10).collect();
+        let codec = HuffmanCodec::new(data.iter().cloned());
+        let enc = codec.encode(&data).unwrap();
+        assert_eq!(HuffmanCodec::<u8>::decode(&enc).unwrap(), data);
+
+        let codec = HuffmanCodec::new("abc".chars());
+        assert!(matches!(
+            codec.encode_str("abd"),
+            Err(HuffErr::UnknownSymbol(100))
+        ));
+        let enc = codec.encode_str("abcabc").unwrap();
+        assert!(matches!(
+            HuffmanCodec::decode_str(&enc[..enc.len() - 2]),
+            Err(HuffErr::Truncated)
+        ));
+        let mut bad = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        bad.extend(&[0; 20]);
+        assert!(matches!(
+            HuffmanCodec::decode_str(&bad),
+            Err(HuffErr::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_header() {
+        // a huge count then a lone leaf root, which would decode forever without reading
+        let mut bad = vec![0xFF; 8];
+        bad.extend(&[0x80 | (b'a' >> 1), b'a' << 7]);
+        assert!(matches!(
+            HuffmanCodec::<u8>::decode(&bad),
+            Err(HuffErr::BadHeader)
+        ));
+        // a real tree but far more symbols claimed than there are bits for
+        let mut enc = HuffmanCodec::new("ab".chars()).encode_str("abba").unwrap();
+        enc[..8].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(
+            HuffmanCodec::decode_str(&enc),
+            Err(HuffErr::Truncated)
+        ));
+    }
+}
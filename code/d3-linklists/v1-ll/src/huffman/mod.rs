@@ -0,0 +1,182 @@
+//! Huffman trees, and a codec that packs their codes into bytes.
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt::{self, Debug};
+use std::io;
+
+mod bits;
+mod canonical;
+mod codec;
+mod stream;
+
+pub use bits::{BitReader, BitWriter};
+pub use canonical::{
+    canonical_codes, code_lengths, limited_lengths, CanonicalCodec, DEFLATE_MAX_BITS,
+};
+pub use codec::HuffmanCodec;
+pub use stream::{compress, decompress};
+
+#[derive(Debug)]
+pub enum HuffErr {
+    /// The codec was built without this symbol, given as Symbol::to_bits
+    UnknownSymbol(u64),
+    /// The buffer ended before everything in the header was read
+    Truncated,
+    /// The header does not hold a valid tree
+    BadHeader,
+    Io(io::Error),
+}
+
+impl fmt::Display for HuffErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HuffErr::UnknownSymbol(b) => write!(f, "Symbol {:#x} not in code table", b),
+            HuffErr::Truncated => write!(f, "Input ended early"),
+            HuffErr::BadHeader => write!(f, "Bad code header"),
+            HuffErr::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HuffErr {}
+
+impl From<io::Error> for HuffErr {
+    fn from(e: io::Error) -> Self {
+        HuffErr::Io(e)
+    }
+}
+
+/// Anything a codec can write into a header, as a fixed number of bits
+pub trait Symbol: Ord + Copy + Debug {
+    const BITS: u8;
+    fn to_bits(self) -> u64;
+    fn from_bits(b: u64) -> Option<Self>;
+}
+
+impl Symbol for u8 {
+    const BITS: u8 = 8;
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+    fn from_bits(b: u64) -> Option<Self> {
+        Some(b as u8)
+    }
+}
+
+impl Symbol for u16 {
+    const BITS: u8 = 16;
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+    fn from_bits(b: u64) -> Option<Self> {
+        Some(b as u16)
+    }
+}
+
+impl Symbol for char {
+    const BITS: u8 = 32;
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+    fn from_bits(b: u64) -> Option<Self> {
+        std::char::from_u32(b as u32)
+    }
+}
+
+#[derive(Debug)]
+pub enum HuffNode<S> {
+    Tree(Box<HuffNode<S>>, Box<HuffNode<S>>),
+    Leaf(S),
+}
+
+pub struct HScore<S> {
+    h: HuffNode<S>,
+    s: u64,
+    // breaks ties in the order nodes were made, so trees come out the same every run
+    seq: usize,
+}
+
+impl<S> PartialEq for HScore<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.s == other.s && self.seq == other.seq
+    }
+}
+
+impl<S> Eq for HScore<S> {}
+
+impl<S> PartialOrd for HScore<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Reversed so the BinaryHeap pops the lowest score first
+impl<S> Ord for HScore<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.s.cmp(&self.s).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<S: Debug> HuffNode<S> {
+    pub fn lfirst_print(&self, depth: i32, dir: char) {
+        match self {
+            HuffNode::Tree(l, r) => {
+                l.lfirst_print(depth + 1, '/');
+                let mut spc = String::new();
+                for _ in 0..depth {
+                    spc.push('.');
+                }
+                println!("{}{}*", spc, dir);
+                r.lfirst_print(depth + 1, '\\');
+            }
+            HuffNode::Leaf(c) => {
+                let mut spc = String::new();
+                for _ in 0..depth {
+                    spc.push('.');
+                }
+                println!("{}{}{:?}", spc, dir, c);
+            }
+        }
+    }
+}
+
+pub fn build_tree<S: Ord + Copy, I: IntoIterator<Item = S>>(syms: I) -> Option<HuffNode<S>> {
+    let mut map = BTreeMap::new();
+    for c in syms {
+        *map.entry(c).or_insert(0) += 1;
+    }
+    tree_from_counts(&map)
+}
+
+/// None when there is nothing to code. A lone symbol goes on both sides
+/// of the root, so it still gets a one bit code.
+pub fn tree_from_counts<S: Ord + Copy>(counts: &BTreeMap<S, u64>) -> Option<HuffNode<S>> {
+    if counts.len() == 1 {
+        let (k, _) = counts.iter().next()?;
+        return Some(HuffNode::Tree(
+            Box::new(HuffNode::Leaf(*k)),
+            Box::new(HuffNode::Leaf(*k)),
+        ));
+    }
+    let mut heap: BinaryHeap<HScore<S>> = counts
+        .iter()
+        .enumerate()
+        .map(|(seq, (k, s))| HScore {
+            h: HuffNode::Leaf(*k),
+            s: *s,
+            seq,
+        })
+        .collect();
+    let mut seq = heap.len();
+    while heap.len() > 1 {
+        let a_node = heap.pop()?;
+        let b_node = heap.pop()?;
+        heap.push(HScore {
+            h: HuffNode::Tree(Box::new(a_node.h), Box::new(b_node.h)),
+            s: a_node.s + b_node.s,
+            seq,
+        });
+        seq += 1;
+    }
+    heap.pop().map(|hs| hs.h)
+}
@@ -0,0 +1,101 @@
+use super::{CanonicalCodec, HuffErr, DEFLATE_MAX_BITS};
+use std::io::{Read, Write};
+
+/// Input is coded in blocks of this many bytes, each with its own tree,
+/// so memory use does not grow with the file and each block fits its own data.
+const BLOCK: u64 = 1 << 16;
+
+/// Writes each block as its coded length (4 bytes, big endian) then the codec output.
+/// Returns the number of bytes written.
+pub fn compress<R: Read, W: Write>(mut r: R, mut w: W) -> Result<u64, HuffErr> {
+    let mut written = 0;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if r.by_ref().take(BLOCK).read_to_end(&mut buf)? == 0 {
+            w.flush()?;
+            return Ok(written);
+        }
+        //256 byte values always fit in 15 bits
+        let codec = CanonicalCodec::new(buf.iter().cloned(), DEFLATE_MAX_BITS).unwrap();
+        let enc = codec.encode(&buf)?;
+        w.write_all(&(enc.len() as u32).to_be_bytes())?;
+        w.write_all(&enc)?;
+        written += 4 + enc.len() as u64;
+    }
+}
+
+/// The length before the next block, or None at a clean end of input
+fn read_len<R: Read>(r: &mut R) -> Result<Option<u32>, HuffErr> {
+    let mut lb = [0; 4];
+    let mut got = 0;
+    while got < 4 {
+        match r.read(&mut lb[got..])? {
+            0 if got == 0 => return Ok(None),
+            0 => return Err(HuffErr::Truncated),
+            n => got += n,
+        }
+    }
+    Ok(Some(u32::from_be_bytes(lb)))
+}
+
+/// Returns the number of bytes written
+pub fn decompress<R: Read, W: Write>(mut r: R, mut w: W) -> Result<u64, HuffErr> {
+    let mut written = 0;
+    let mut buf = Vec::new();
+    while let Some(len) = read_len(&mut r)? {
+        buf.clear();
+        if r.by_ref().take(len as u64).read_to_end(&mut buf)? < len as usize {
+            return Err(HuffErr::Truncated);
+        }
+        let dec = CanonicalCodec::<u8>::decode(&buf)?;
+        w.write_all(&dec)?;
+        written += dec.len() as u64;
+    }
+    w.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> usize {
+        let mut enc = Vec::new();
+        let n = compress(data, &mut enc).unwrap();
+        assert_eq!(n as usize, enc.len());
+        let mut dec = Vec::new();
+        decompress(&enc[..], &mut dec).unwrap();
+        assert_eq!(dec, data);
+        enc.len()
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let mut log = String::new();
+        for i in 0..6000 {
+            log.push_str(&format!(
+                "2020-01-01 12:00:{:02} INFO request {} ok\n",
+                i % 60,
+                i
+            ));
+        }
+        assert!(log.len() as u64 > 3 * BLOCK);
+        let clen = round_trip(log.as_bytes());
+        assert!(clen < log.len() * 2 / 3, "{} of {}", clen, log.len());
+
+        assert_eq!(round_trip(b""), 0);
+        round_trip(b"x");
+        round_trip(&[7; 1000]);
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let mut enc = Vec::new();
+        compress(&b"some text to squash"[..], &mut enc).unwrap();
+        for cut in &[2, 6, enc.len() - 1] {
+            let mut dec = Vec::new();
+            assert!(decompress(&enc[..*cut], &mut dec).is_err());
+        }
+    }
+}
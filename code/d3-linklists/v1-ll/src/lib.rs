@@ -0,0 +1,3 @@
+//! Code shared by more than one of the bins
+pub mod compress;
+pub mod huffman;
@@ -0,0 +1,183 @@
+use super::{Meta, Node, RangeIter, SortedMap, Tree};
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/// Key/value version of the height balanced BinTree from v4_redblack
+#[derive(Debug)]
+pub struct AvlMap<K, V> {
+    root: Tree<K, V, Height>,
+    len: usize,
+}
+
+#[derive(Debug)]
+pub struct Height(i8);
+
+impl Meta for Height {
+    fn update<K, V>(n: &mut Node<K, V, Self>) {
+        n.m.0 = 1 + std::cmp::max(n.left.height(), n.right.height());
+    }
+}
+
+impl<K, V> Default for AvlMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> AvlMap<K, V> {
+    pub fn new() -> Self {
+        AvlMap {
+            root: Tree(None),
+            len: 0,
+        }
+    }
+}
+
+impl<K, V> Tree<K, V, Height> {
+    pub fn height(&self) -> i8 {
+        match self.0 {
+            Some(ref t) => t.m.0,
+            None => 0,
+        }
+    }
+
+    /// Fix heights on the way back up, rotating where one side
+    /// has got more than one taller than the other
+    fn rebalance(&mut self) {
+        let n = match self.0 {
+            Some(ref mut n) => n,
+            None => return,
+        };
+        let diff = n.left.height() - n.right.height();
+        if diff > 1 {
+            let l = n.left.0.as_ref().unwrap(); // diff > 1 so left exists
+            if l.right.height() > l.left.height() {
+                n.left.rot_left();
+            }
+            self.rot_right();
+        } else if diff < -1 {
+            let r = n.right.0.as_ref().unwrap();
+            if r.left.height() > r.right.height() {
+                n.right.rot_right();
+            }
+            self.rot_left();
+        } else {
+            Height::update(n);
+        }
+    }
+
+    /// pop_first, but keeping every node on the way balanced
+    fn avl_pop_first(&mut self) -> Option<Box<Node<K, V, Height>>> {
+        let n = self.0.as_mut()?;
+        if n.left.0.is_some() {
+            let res = n.left.avl_pop_first();
+            self.rebalance();
+            return res;
+        }
+        let mut n = self.0.take()?;
+        self.0 = n.right.0.take();
+        Some(n)
+    }
+}
+
+impl<K: Ord, V> Tree<K, V, Height> {
+    fn avl_insert(&mut self, k: K, v: V) -> Option<V> {
+        let res = match self.0 {
+            Some(ref mut n) => match k.cmp(&n.k) {
+                Ordering::Less => n.left.avl_insert(k, v),
+                Ordering::Greater => n.right.avl_insert(k, v),
+                Ordering::Equal => return Some(std::mem::replace(&mut n.v, v)),
+            },
+            None => {
+                self.0 = Some(Node::new(k, v, Height(1)));
+                return None;
+            }
+        };
+        self.rebalance();
+        res
+    }
+
+    fn avl_remove(&mut self, k: &K) -> Option<V> {
+        let n = self.0.as_mut()?;
+        let res = match k.cmp(&n.k) {
+            Ordering::Less => n.left.avl_remove(k),
+            Ordering::Greater => n.right.avl_remove(k),
+            Ordering::Equal => {
+                let mut n = self.0.take()?;
+                self.0 = match n.right.avl_pop_first() {
+                    Some(mut first) => {
+                        first.left = Tree(n.left.0.take());
+                        first.right = Tree(n.right.0.take());
+                        Some(first)
+                    }
+                    None => n.left.0.take(),
+                };
+                Some(n.v)
+            }
+        };
+        self.rebalance();
+        res
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> for AvlMap<K, V> {
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let res = self.root.avl_insert(k, v);
+        if res.is_none() {
+            self.len += 1;
+        }
+        res
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        self.root.get(k)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        let res = self.root.avl_remove(k);
+        if res.is_some() {
+            self.len -= 1;
+        }
+        res
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> RangeIter<'a, K, V> {
+        Box::new(self.root.range(lo, hi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_balanced<K, V>(t: &Tree<K, V, Height>) -> i8 {
+        match t.0 {
+            Some(ref n) => {
+                let l = check_balanced(&n.left);
+                let r = check_balanced(&n.right);
+                assert!((l - r).abs() <= 1);
+                assert_eq!(n.m.0, 1 + std::cmp::max(l, r));
+                n.m.0
+            }
+            None => 0,
+        }
+    }
+
+    #[test]
+    fn test_stays_balanced() {
+        let mut m = AvlMap::new();
+        for i in 0..1000 {
+            m.insert(i, i);
+        }
+        check_balanced(&m.root);
+        assert!(m.root.height() <= 14);
+        for i in (0..1000).step_by(3) {
+            m.remove(&i);
+        }
+        check_balanced(&m.root);
+    }
+}
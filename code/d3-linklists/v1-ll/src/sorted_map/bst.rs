@@ -0,0 +1,91 @@
+use super::{Node, RangeIter, SortedMap, Tree};
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/// Plain unbalanced tree, the baseline the others are measured against
+#[derive(Debug)]
+pub struct BstMap<K, V> {
+    root: Tree<K, V, ()>,
+    len: usize,
+}
+
+impl<K, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> BstMap<K, V> {
+    pub fn new() -> Self {
+        BstMap {
+            root: Tree(None),
+            len: 0,
+        }
+    }
+}
+
+impl<K: Ord, V> Tree<K, V, ()> {
+    fn bst_insert(&mut self, k: K, v: V) -> Option<V> {
+        match self.0 {
+            Some(ref mut n) => match k.cmp(&n.k) {
+                Ordering::Less => n.left.bst_insert(k, v),
+                Ordering::Greater => n.right.bst_insert(k, v),
+                Ordering::Equal => Some(std::mem::replace(&mut n.v, v)),
+            },
+            None => {
+                self.0 = Some(Node::new(k, v, ()));
+                None
+            }
+        }
+    }
+
+    fn bst_remove(&mut self, k: &K) -> Option<V> {
+        let n = self.0.as_mut()?;
+        match k.cmp(&n.k) {
+            Ordering::Less => n.left.bst_remove(k),
+            Ordering::Greater => n.right.bst_remove(k),
+            Ordering::Equal => {
+                let mut n = self.0.take()?;
+                self.0 = match n.right.pop_first() {
+                    Some(mut first) => {
+                        first.left = Tree(n.left.0.take());
+                        first.right = Tree(n.right.0.take());
+                        Some(first)
+                    }
+                    None => n.left.0.take(),
+                };
+                Some(n.v)
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> for BstMap<K, V> {
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let res = self.root.bst_insert(k, v);
+        if res.is_none() {
+            self.len += 1;
+        }
+        res
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        self.root.get(k)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        let res = self.root.bst_remove(k);
+        if res.is_some() {
+            self.len -= 1;
+        }
+        res
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> RangeIter<'a, K, V> {
+        Box::new(self.root.range(lo, hi))
+    }
+}
@@ -0,0 +1,256 @@
+//! Several binary search trees behind one SortedMap trait
+//! so they can be tested and timed against each other.
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+pub mod avl;
+pub mod bst;
+pub mod splay;
+pub mod treap;
+
+pub use avl::AvlMap;
+pub use bst::BstMap;
+pub use splay::SplayMap;
+pub use treap::TreapMap;
+
+pub type RangeIter<'a, K, V> = Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>;
+
+pub trait SortedMap<K: Ord, V> {
+    /// Returns the old value if k was already present
+    fn insert(&mut self, k: K, v: V) -> Option<V>;
+    /// Takes &mut so self adjusting trees can reorganise on lookup
+    fn get(&mut self, k: &K) -> Option<&V>;
+    fn remove(&mut self, k: &K) -> Option<V>;
+    fn len(&self) -> usize;
+    fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> RangeIter<'a, K, V>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> RangeIter<'_, K, V> {
+        self.range(Bound::Unbounded, Bound::Unbounded)
+    }
+}
+
+/// Helper for callers with a normal range like 3..7
+pub fn range_of<'a, K: Ord, V, M: SortedMap<K, V>, R: RangeBounds<K>>(
+    m: &'a M,
+    r: &'a R,
+) -> RangeIter<'a, K, V> {
+    m.range(r.start_bound(), r.end_bound())
+}
+
+/// All the trees share this shape, M is whatever the balancing scheme
+/// needs to keep per node (height, priority or nothing)
+#[derive(Debug)]
+pub struct Tree<K, V, M>(Option<Box<Node<K, V, M>>>);
+
+#[derive(Debug)]
+pub struct Node<K, V, M> {
+    k: K,
+    v: V,
+    m: M,
+    left: Tree<K, V, M>,
+    right: Tree<K, V, M>,
+}
+
+/// Called after a node's children change, so heights etc can be fixed
+pub trait Meta: Sized {
+    fn update<K, V>(_n: &mut Node<K, V, Self>) {}
+}
+
+impl Meta for () {}
+
+impl<K, V, M: Meta> Node<K, V, M> {
+    pub fn new(k: K, v: V, m: M) -> Box<Self> {
+        Box::new(Node {
+            k,
+            v,
+            m,
+            left: Tree(None),
+            right: Tree(None),
+        })
+    }
+
+    pub fn rot_left(mut self: Box<Self>) -> Box<Self> {
+        let mut res = match self.right.0.take() {
+            Some(res) => res,
+            None => return self,
+        };
+        self.right = Tree(res.left.0.take());
+        M::update(&mut self);
+        res.left = Tree(Some(self));
+        M::update(&mut res);
+        res
+    }
+
+    pub fn rot_right(mut self: Box<Self>) -> Box<Self> {
+        let mut res = match self.left.0.take() {
+            Some(res) => res,
+            None => return self,
+        };
+        self.left = Tree(res.right.0.take());
+        M::update(&mut self);
+        res.right = Tree(Some(self));
+        M::update(&mut res);
+        res
+    }
+}
+
+impl<K, V, M> Default for Tree<K, V, M> {
+    fn default() -> Self {
+        Tree(None)
+    }
+}
+
+impl<K, V, M: Meta> Tree<K, V, M> {
+    pub fn rot_left(&mut self) {
+        self.0 = self.0.take().map(|v| v.rot_left());
+    }
+    pub fn rot_right(&mut self) {
+        self.0 = self.0.take().map(|v| v.rot_right());
+    }
+
+    /// Unlinks the smallest node, used to fill the gap left by a remove
+    pub fn pop_first(&mut self) -> Option<Box<Node<K, V, M>>> {
+        let n = self.0.as_mut()?;
+        if n.left.0.is_some() {
+            let res = n.left.pop_first();
+            M::update(n);
+            return res;
+        }
+        let mut n = self.0.take()?;
+        self.0 = n.right.0.take();
+        Some(n)
+    }
+}
+
+impl<K: Ord, V, M> Tree<K, V, M> {
+    pub fn get(&self, k: &K) -> Option<&V> {
+        let mut curr = self;
+        while let Some(ref n) = curr.0 {
+            curr = match k.cmp(&n.k) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return Some(&n.v),
+            };
+        }
+        None
+    }
+
+    pub fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> Range<'a, K, V, M> {
+        let mut res = Range {
+            stack: Vec::new(),
+            hi,
+        };
+        // Only push the nodes that could be at or after lo
+        let mut curr = self;
+        while let Some(ref n) = curr.0 {
+            let after_lo = match lo {
+                Bound::Included(l) => n.k >= *l,
+                Bound::Excluded(l) => n.k > *l,
+                Bound::Unbounded => true,
+            };
+            if after_lo {
+                res.stack.push(n);
+                curr = &n.left;
+            } else {
+                curr = &n.right;
+            }
+        }
+        res
+    }
+}
+
+pub struct Range<'a, K, V, M> {
+    stack: Vec<&'a Node<K, V, M>>,
+    hi: Bound<&'a K>,
+}
+
+impl<'a, K: Ord, V, M> Iterator for Range<'a, K, V, M> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.stack.pop()?;
+        let in_range = match self.hi {
+            Bound::Included(h) => n.k <= *h,
+            Bound::Excluded(h) => n.k < *h,
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.stack.clear();
+            return None;
+        }
+        let mut curr = &n.right;
+        while let Some(ref c) = curr.0 {
+            self.stack.push(c);
+            curr = &c.left;
+        }
+        Some((&n.k, &n.v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::RandGen;
+    use std::collections::BTreeMap;
+
+    /// Every implementation must behave exactly like std's BTreeMap
+    pub fn conformance<M: SortedMap<usize, usize> + Default>() {
+        let mut m = M::default();
+        let mut model = BTreeMap::new();
+        let mut rg = RandGen::seeded(77);
+        assert!(m.is_empty());
+        for _ in 0..5000 {
+            let k = rg.next().unwrap() % 300;
+            let v = rg.next().unwrap();
+            match rg.next().unwrap() % 4 {
+                0 | 1 => assert_eq!(m.insert(k, v), model.insert(k, v)),
+                2 => assert_eq!(m.remove(&k), model.remove(&k)),
+                _ => assert_eq!(m.get(&k), model.get(&k)),
+            }
+            assert_eq!(m.len(), model.len());
+        }
+        assert!(m.iter().eq(model.iter()));
+
+        for (lo, hi) in [(0, 300), (10, 20), (50, 51), (299, 400), (20, 10)] {
+            let lo_hi = lo..hi;
+            if lo <= hi {
+                assert!(range_of(&m, &lo_hi).eq(model.range(lo..hi)));
+                assert!(range_of(&m, &(lo..=hi)).eq(model.range(lo..=hi)));
+            } else {
+                assert_eq!(range_of(&m, &lo_hi).count(), 0);
+            }
+        }
+        let excl = m.range(Bound::Excluded(&10), Bound::Unbounded);
+        assert!(excl.eq(model.range((Bound::Excluded(10), Bound::Unbounded))));
+
+        let keys: Vec<usize> = model.keys().cloned().collect();
+        for k in keys {
+            assert_eq!(m.remove(&k), model.remove(&k));
+        }
+        assert!(m.is_empty());
+        assert_eq!(m.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_bst_conforms() {
+        conformance::<BstMap<_, _>>();
+    }
+
+    #[test]
+    fn test_avl_conforms() {
+        conformance::<AvlMap<_, _>>();
+    }
+
+    #[test]
+    fn test_splay_conforms() {
+        conformance::<SplayMap<_, _>>();
+    }
+
+    #[test]
+    fn test_treap_conforms() {
+        conformance::<TreapMap<_, _>>();
+    }
+}
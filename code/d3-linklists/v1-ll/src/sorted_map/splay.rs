@@ -0,0 +1,172 @@
+use super::{Node, RangeIter, SortedMap, Tree};
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/// Self adjusting tree, every access rotates the found (or nearest) node
+/// to the root, so recently used keys are cheap to reach again.
+#[derive(Debug)]
+pub struct SplayMap<K, V> {
+    root: Tree<K, V, ()>,
+    len: usize,
+}
+
+impl<K, V> Default for SplayMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> SplayMap<K, V> {
+    pub fn new() -> Self {
+        SplayMap {
+            root: Tree(None),
+            len: 0,
+        }
+    }
+}
+
+/// Bring k, or the last node on its search path, to the top.
+/// Pairs of steps in the same direction (zig-zig) rotate the grandparent first,
+/// that is what keeps the amortised cost at O(log n).
+fn splay<K: Ord, V>(mut t: Box<Node<K, V, ()>>, k: &K) -> Box<Node<K, V, ()>> {
+    match k.cmp(&t.k) {
+        Ordering::Less => {
+            let l = match t.left.0.as_mut() {
+                Some(l) => l,
+                None => return t,
+            };
+            match k.cmp(&l.k) {
+                Ordering::Less => {
+                    if let Some(ll) = l.left.0.take() {
+                        l.left = Tree(Some(splay(ll, k)));
+                    }
+                    t = t.rot_right();
+                }
+                Ordering::Greater => {
+                    if let Some(lr) = l.right.0.take() {
+                        l.right = Tree(Some(splay(lr, k)));
+                        t.left.rot_left();
+                    }
+                }
+                Ordering::Equal => {}
+            }
+            t.rot_right()
+        }
+        Ordering::Greater => {
+            let r = match t.right.0.as_mut() {
+                Some(r) => r,
+                None => return t,
+            };
+            match k.cmp(&r.k) {
+                Ordering::Greater => {
+                    if let Some(rr) = r.right.0.take() {
+                        r.right = Tree(Some(splay(rr, k)));
+                    }
+                    t = t.rot_left();
+                }
+                Ordering::Less => {
+                    if let Some(rl) = r.left.0.take() {
+                        r.left = Tree(Some(splay(rl, k)));
+                        t.right.rot_right();
+                    }
+                }
+                Ordering::Equal => {}
+            }
+            t.rot_left()
+        }
+        Ordering::Equal => t,
+    }
+}
+
+impl<K: Ord, V> SplayMap<K, V> {
+    fn splay_root(&mut self, k: &K) -> Option<&mut Box<Node<K, V, ()>>> {
+        let root = self.root.0.take()?;
+        self.root.0 = Some(splay(root, k));
+        self.root.0.as_mut()
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> for SplayMap<K, V> {
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let mut root = match self.root.0.take() {
+            Some(r) => splay(r, &k),
+            None => {
+                self.root = Tree(Some(Node::new(k, v, ())));
+                self.len += 1;
+                return None;
+            }
+        };
+        // The new node goes on top, and the old root's subtree is split around it
+        let mut nn = Node::new(k, v, ());
+        match nn.k.cmp(&root.k) {
+            Ordering::Equal => {
+                let res = std::mem::replace(&mut root.v, nn.v);
+                self.root = Tree(Some(root));
+                return Some(res);
+            }
+            Ordering::Less => {
+                nn.left = Tree(root.left.0.take());
+                nn.right = Tree(Some(root));
+            }
+            Ordering::Greater => {
+                nn.right = Tree(root.right.0.take());
+                nn.left = Tree(Some(root));
+            }
+        }
+        self.root = Tree(Some(nn));
+        self.len += 1;
+        None
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        let root = self.splay_root(k)?;
+        if root.k == *k {
+            Some(&root.v)
+        } else {
+            None
+        }
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        if self.splay_root(k)?.k != *k {
+            return None;
+        }
+        let mut root = self.root.0.take()?;
+        // Everything on the left is smaller than k, so splaying k there
+        // brings the largest to the top with an empty right side
+        self.root.0 = match root.left.0.take() {
+            Some(l) => {
+                let mut l = splay(l, k);
+                l.right = Tree(root.right.0.take());
+                Some(l)
+            }
+            None => root.right.0.take(),
+        };
+        self.len -= 1;
+        Some(root.v)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> RangeIter<'a, K, V> {
+        Box::new(self.root.range(lo, hi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_moves_to_root() {
+        let mut m = SplayMap::new();
+        for i in 0..100 {
+            m.insert(i, i * 2);
+        }
+        assert_eq!(m.get(&37), Some(&74));
+        assert_eq!(m.root.0.as_ref().unwrap().k, 37);
+        assert_eq!(m.get(&1000), None);
+    }
+}
@@ -0,0 +1,160 @@
+use super::{Meta, Node, RangeIter, SortedMap, Tree};
+use crate::b_rand::RandGen;
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/// Tree on the keys, heap on a random priority.
+/// Random priorities give the same shape as inserting in random order,
+/// so the expected depth is O(log n) whatever order the keys arrive in.
+#[derive(Debug)]
+pub struct TreapMap<K, V> {
+    root: Tree<K, V, Priority>,
+    len: usize,
+    rg: RandGen,
+}
+
+#[derive(Debug)]
+pub struct Priority(usize);
+
+impl Meta for Priority {}
+
+impl<K, V> Default for TreapMap<K, V> {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl<K, V> TreapMap<K, V> {
+    pub fn new(seed: usize) -> Self {
+        TreapMap {
+            root: Tree(None),
+            len: 0,
+            rg: RandGen::seeded(seed),
+        }
+    }
+}
+
+impl<K, V> Tree<K, V, Priority> {
+    fn priority(&self) -> Option<usize> {
+        self.0.as_ref().map(|n| n.m.0)
+    }
+}
+
+impl<K: Ord, V> Tree<K, V, Priority> {
+    fn treap_insert(&mut self, k: K, v: V, p: usize) -> Option<V> {
+        let n = match self.0 {
+            Some(ref mut n) => n,
+            None => {
+                self.0 = Some(Node::new(k, v, Priority(p)));
+                return None;
+            }
+        };
+        // After inserting below, lift the child if it beats us on priority
+        match k.cmp(&n.k) {
+            Ordering::Less => {
+                let res = n.left.treap_insert(k, v, p);
+                if n.left.priority() > Some(n.m.0) {
+                    self.rot_right();
+                }
+                res
+            }
+            Ordering::Greater => {
+                let res = n.right.treap_insert(k, v, p);
+                if n.right.priority() > Some(n.m.0) {
+                    self.rot_left();
+                }
+                res
+            }
+            Ordering::Equal => Some(std::mem::replace(&mut n.v, v)),
+        }
+    }
+
+    fn treap_remove(&mut self, k: &K) -> Option<V> {
+        let n = self.0.as_mut()?;
+        match k.cmp(&n.k) {
+            Ordering::Less => n.left.treap_remove(k),
+            Ordering::Greater => n.right.treap_remove(k),
+            // Rotate the node down below its higher priority child until it has
+            // at most one child, then splice it out
+            Ordering::Equal => match (n.left.priority(), n.right.priority()) {
+                (Some(lp), Some(rp)) => {
+                    if lp > rp {
+                        self.rot_right();
+                        self.0.as_mut()?.right.treap_remove(k)
+                    } else {
+                        self.rot_left();
+                        self.0.as_mut()?.left.treap_remove(k)
+                    }
+                }
+                (Some(_), None) => {
+                    let mut n = self.0.take()?;
+                    self.0 = n.left.0.take();
+                    Some(n.v)
+                }
+                (None, _) => {
+                    let mut n = self.0.take()?;
+                    self.0 = n.right.0.take();
+                    Some(n.v)
+                }
+            },
+        }
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> for TreapMap<K, V> {
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let p = self.rg.next().unwrap_or(0);
+        let res = self.root.treap_insert(k, v, p);
+        if res.is_none() {
+            self.len += 1;
+        }
+        res
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        self.root.get(k)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        let res = self.root.treap_remove(k);
+        if res.is_some() {
+            self.len -= 1;
+        }
+        res
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn range<'a>(&'a self, lo: Bound<&K>, hi: Bound<&'a K>) -> RangeIter<'a, K, V> {
+        Box::new(self.root.range(lo, hi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_heap<K, V>(t: &Tree<K, V, Priority>, parent: usize) {
+        if let Some(ref n) = t.0 {
+            assert!(n.m.0 <= parent);
+            check_heap(&n.left, n.m.0);
+            check_heap(&n.right, n.m.0);
+        }
+    }
+
+    #[test]
+    fn test_heap_order_kept() {
+        let mut m = TreapMap::new(5);
+        for i in 0..500 {
+            m.insert(i, i);
+        }
+        check_heap(&m.root, usize::MAX);
+        for i in (0..500).step_by(2) {
+            m.remove(&i);
+        }
+        check_heap(&m.root, usize::MAX);
+        assert_eq!(m.len(), 250);
+    }
+}
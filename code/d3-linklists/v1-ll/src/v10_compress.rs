@@ -0,0 +1,76 @@
+use ll::compress::Method;
+use std::env;
+use std::fs;
+use std::process;
+
+fn usage() {
+    eprintln!("usage: v10_compress <file>              compare every method on a file");
+    eprintln!("       v10_compress c <method> <in> <out>");
+    eprintln!("       v10_compress d <method> <in> <out>");
+    let names: Vec<&str> = Method::ALL.iter().map(|m| m.name()).collect();
+    eprintln!("methods: {}", names.join(", "));
+}
+
+fn compare(path: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut failed = false;
+    for m in &Method::ALL {
+        match m.compress(&data) {
+            Ok(enc) => {
+                let ok = m.decompress(&enc).map(|d| d == data).unwrap_or(false);
+                println!(
+                    "{:8} {} -> {} bytes, ratio {:.3}, round trip ok = {}",
+                    m.name(),
+                    data.len(),
+                    enc.len(),
+                    enc.len() as f64 / data.len().max(1) as f64,
+                    ok
+                );
+                failed |= !ok;
+            }
+            Err(e) => {
+                eprintln!("{:8} failed: {}", m.name(), e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err("not every method round tripped".to_string());
+    }
+    Ok(())
+}
+
+fn run(mode: &str, method: &str, inp: &str, out: &str) -> Result<(), String> {
+    let m = Method::from_name(method).ok_or_else(|| format!("no method {}", method))?;
+    let data = fs::read(inp).map_err(|e| format!("could not read {}: {}", inp, e))?;
+    let res = match mode {
+        "c" => m.compress(&data),
+        _ => m.decompress(&data),
+    }
+    .map_err(|e| format!("{} failed: {}", m.name(), e))?;
+    fs::write(out, &res).map_err(|e| format!("could not write {}: {}", out, e))?;
+    println!(
+        "{} {} bytes -> {} {} bytes",
+        inp,
+        data.len(),
+        out,
+        res.len()
+    );
+    Ok(())
+}
+
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let res = match args.len() {
+        1 => compare(&args[0]),
+        4 if args[0] == "c" || args[0] == "d" => run(&args[0], &args[1], &args[2], &args[3]),
+        _ => {
+            usage();
+            Err("bad arguments".to_string())
+        }
+    };
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
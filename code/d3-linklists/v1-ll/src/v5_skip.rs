@@ -1,6 +1,11 @@
+mod b_rand;
+
+use b_rand::RandGen;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::{Debug, Write};
+use std::ops::{Bound, RangeBounds};
 use std::rc::Rc;
 type Rcc<T> = Rc<RefCell<T>>;
 pub fn rcc<T>(t: T) -> Rcc<T> {
@@ -22,41 +27,6 @@ impl<T: PartialOrd> SkipNode<T> {
             data: rcc(t),
         }
     }
-
-    pub fn insert(&mut self, dt: T) -> Option<Rcc<SkipNode<T>>> {
-        //bigger than right
-        if let Some(ref mut rt) = self.right {
-            if dt > *rt.borrow().data.borrow() {
-                return rt.borrow_mut().insert(dt);
-            }
-        }
-        //has lower children
-        if let Some(ref dw) = self.down {
-            return match dw.borrow_mut().insert(dt) {
-                Some(child) => match rand::random::<bool>() {
-                    true => {
-                        let dt = child.borrow().data.clone();
-                        let nn = SkipNode {
-                            right: self.right.take(),
-                            data: dt,
-                            down: Some(child),
-                        };
-                        let res = rcc(nn);
-                        self.right = Some(res.clone());
-                        Some(res)
-                    }
-                    false => None,
-                },
-                None => None,
-            };
-        }
-        //bottom row dont move right
-        let mut nn = SkipNode::new(dt);
-        nn.right = self.right.take();
-        let res = rcc(nn);
-        self.right = Some(res.clone());
-        Some(res)
-    }
 }
 impl<T: Debug + PartialOrd> SkipNode<T> {
     pub fn print_row<W: Write>(&self, w: &mut W) {
@@ -67,76 +37,382 @@ impl<T: Debug + PartialOrd> SkipNode<T> {
     }
 }
 
+/// A place on one level, None is before the first node of that level
+type Pos<T> = Option<Rcc<SkipNode<T>>>;
+
+fn down_of<T: PartialOrd>(p: &Pos<T>) -> Pos<T> {
+    p.as_ref().and_then(|p| p.borrow().down.clone())
+}
+
+fn before_start<T: PartialOrd>(b: Bound<&T>, t: &T) -> bool {
+    match b {
+        Bound::Included(s) => t < s,
+        Bound::Excluded(s) => t <= s,
+        Bound::Unbounded => false,
+    }
+}
+
+fn before_end<T: PartialOrd>(b: Bound<&T>, t: &T) -> bool {
+    match b {
+        Bound::Included(e) => t <= e,
+        Bound::Excluded(e) => t < e,
+        Bound::Unbounded => true,
+    }
+}
+
+//promotion draws are compared at this resolution
+const P_STEPS: usize = 1 << 16;
+
 #[derive(Debug)]
 /// zeroth element will be the bottom
-pub struct SkipList<T: PartialOrd>(Vec<SkipNode<T>>);
+/// Each node is copied to the level above with probability p, up to max_level levels,
+/// using draws from G so the shape of the list can be repeated from a seed.
+pub struct SkipList<T: PartialOrd, G = RandGen> {
+    heads: Vec<Pos<T>>,
+    len: usize,
+    rng: G,
+    p: f64,
+    max_level: usize,
+}
 
-impl<T: PartialOrd + Debug> SkipList<T> {
+impl<T: PartialOrd> Default for SkipList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PartialOrd> SkipList<T> {
     pub fn new() -> Self {
-        SkipList(Vec::new())
+        Self::with_rng(RandGen::seeded(1), 0.5, 32)
+    }
+}
+
+impl<T: PartialOrd, G: Iterator<Item = usize>> SkipList<T, G> {
+    /// Lower p gives fewer, longer rows: less memory but longer walks on each row
+    pub fn with_rng(rng: G, p: f64, max_level: usize) -> Self {
+        assert!(
+            p > 0.0 && p < 1.0,
+            "promotion probability must be between 0 and 1"
+        );
+        assert!(max_level > 0, "need at least the bottom level");
+        SkipList {
+            heads: Vec::new(),
+            len: 0,
+            rng,
+            p,
+            max_level,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
-    /// return whether the function parent should consider adding a ref
+    fn next_of(&self, pos: &Pos<T>, level: usize) -> Pos<T> {
+        match pos {
+            Some(n) => n.borrow().right.clone(),
+            None => self.heads[level].clone(),
+        }
+    }
+
+    fn set_next(&mut self, pos: &Pos<T>, level: usize, nx: Pos<T>) {
+        match pos {
+            Some(n) => n.borrow_mut().right = nx,
+            None => self.heads[level] = nx,
+        }
+    }
+
+    /// Go right from pos while the next node is still before the target
+    fn walk<F: Fn(&T) -> bool>(&self, mut pos: Pos<T>, level: usize, before: &F) -> Pos<T> {
+        while let Some(nx) = self.next_of(&pos, level) {
+            if !before(&nx.borrow().data.borrow()) {
+                break;
+            }
+            pos = Some(nx);
+        }
+        pos
+    }
+
+    /// The last node before the target on every level, found by going
+    /// as far right as possible on each level before dropping down
+    fn path<F: Fn(&T) -> bool>(&self, before: &F) -> Vec<Pos<T>> {
+        let mut res = vec![None; self.heads.len()];
+        let mut pos = None;
+        for level in (0..self.heads.len()).rev() {
+            pos = self.walk(pos, level, before);
+            res[level] = pos.clone();
+            pos = down_of(&pos);
+        }
+        res
+    }
+
+    /// Bottom row iterator from the first item not before the target
+    fn seek<F: Fn(&T) -> bool>(&self, before: F) -> Iter<T> {
+        match self.path(&before).first() {
+            Some(pos) => Iter {
+                next: self.next_of(pos, 0),
+            },
+            None => Iter { next: None },
+        }
+    }
+
+    /// Equal items stay in the order they were added
     pub fn insert(&mut self, data: T) {
-        if self.0.len() == 0 {
-            self.0.push(SkipNode::new(data));
-            return;
-        }
-        //insert after fisrt place if fits after
-        for i in (0..self.0.len()).rev() {
-            if data > *self.0[i].data.borrow() {
-                if let Some(ch) = self.0[i].insert(data) {
-                    self.loop_up(ch, i + 1);
+        let path = self.path(&|x| *x <= data);
+        let mut nn = SkipNode::new(data);
+        let data = nn.data.clone();
+        let mut level = 0;
+        loop {
+            let pos = path.get(level).cloned().flatten();
+            if level == self.heads.len() {
+                self.heads.push(None);
+            }
+            nn.right = self.next_of(&pos, level);
+            let res = rcc(nn);
+            self.set_next(&pos, level, Some(res.clone()));
+            level += 1;
+            if level == self.max_level || !self.promote() {
+                break;
+            }
+            nn = SkipNode {
+                right: None,
+                down: Some(res),
+                data: data.clone(),
+            };
+        }
+        self.len += 1;
+    }
+
+    fn promote(&mut self) -> bool {
+        match self.rng.next() {
+            Some(r) => ((r % P_STEPS) as f64) < self.p * P_STEPS as f64,
+            None => false,
+        }
+    }
+
+    /// Number of nodes on each level, bottom first
+    pub fn level_histogram(&self) -> Vec<usize> {
+        self.heads
+            .iter()
+            .map(|h| {
+                let mut n = h.clone();
+                let mut count = 0;
+                while let Some(nd) = n {
+                    count += 1;
+                    n = nd.borrow().right.clone();
                 }
+                count
+            })
+            .collect()
+    }
 
-                return;
+    /// Unlink one item matching cmp from every level its tower reaches
+    fn remove_by<F: Fn(&T) -> Option<Ordering>>(&mut self, cmp: F) -> Option<Rcc<T>> {
+        let path = self.path(&|x| cmp(x) == Some(Ordering::Less));
+        let mut found: Option<Rcc<T>> = None;
+        for level in (0..path.len()).rev() {
+            // With repeats the tower we want may be behind other equal ones
+            let mut pos = path[level].clone();
+            while let Some(nx) = self.next_of(&pos, level) {
+                if cmp(&nx.borrow().data.borrow()) != Some(Ordering::Equal) {
+                    break;
+                }
+                let ours = match found {
+                    Some(ref f) => Rc::ptr_eq(f, &nx.borrow().data),
+                    None => true,
+                };
+                if ours {
+                    let nb = nx.borrow();
+                    found = Some(nb.data.clone());
+                    self.set_next(&pos, level, nb.right.clone());
+                    break;
+                }
+                pos = Some(nx);
             }
         }
-        //insert as first element
-        let mut nn = SkipNode::new(data);
-        std::mem::swap(&mut nn, &mut self.0[0]);
-        let res = rcc(nn);
-        self.0[0].right = Some(res.clone());
-        //TODO go up
-        self.loop_up(res, 1);
+        while let Some(None) = self.heads.last() {
+            self.heads.pop();
+        }
+        let res = found?;
+        self.len -= 1;
+        Some(res)
+    }
+
+    pub fn remove(&mut self, t: &T) -> Option<Rcc<T>> {
+        self.remove_by(|x| x.partial_cmp(t))
     }
 
-    pub fn loop_up(&mut self, ch: Rcc<SkipNode<T>>, n: usize) {
-        if rand::random::<bool>() == true {
-            return;
+    pub fn get(&self, t: &T) -> Option<Rcc<T>> {
+        let d = self.seek(|x| x < t).next()?;
+        if *d.borrow() == *t {
+            Some(d)
+        } else {
+            None
         }
-        let dt = ch.borrow().data.clone();
-        let mut nn = SkipNode {
-            right: None,
-            down: Some(ch),
-            data: dt,
-        };
-        if n >= self.0.len() {
-            self.0.push(nn);
-            return;
+    }
+
+    pub fn contains(&self, t: &T) -> bool {
+        self.get(t).is_some()
+    }
+
+    pub fn first(&self) -> Option<Rcc<T>> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<Rcc<T>> {
+        let path = self.path(&|_| true);
+        path.first()?.as_ref().map(|n| n.borrow().data.clone())
+    }
+
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            next: self.heads.first().cloned().flatten(),
         }
+    }
+
+    pub fn range<R: RangeBounds<T>>(&self, r: R) -> impl Iterator<Item = Rcc<T>> {
+        self.seek(|x| before_start(r.start_bound(), x))
+            .take_while(move |d| before_end(r.end_bound(), &d.borrow()))
+    }
+}
+
+/// Walks the bottom row, which holds every item
+pub struct Iter<T: PartialOrd> {
+    next: Pos<T>,
+}
 
-        std::mem::swap(&mut nn, &mut self.0[n]);
-        let res = rcc(nn);
-        self.0[n].right = Some(res.clone());
-        self.loop_up(res, n + 1);
+impl<T: PartialOrd> Iterator for Iter<T> {
+    type Item = Rcc<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.next.take()?;
+        let nb = n.borrow();
+        self.next = nb.right.clone();
+        Some(nb.data.clone())
     }
 }
 
-impl<T: Debug + PartialOrd> fmt::Display for SkipList<T> {
+impl<T: Debug + PartialOrd, G> fmt::Display for SkipList<T, G> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.0.len() == 0 {
+        if self.heads.is_empty() {
             write!(f, "SkipList<Empty>")?;
             return Ok(());
         }
-        for sn in &self.0 {
-            write!(f, "\n")?;
-            sn.print_row(f);
+        for h in self.heads.iter().flatten() {
+            writeln!(f)?;
+            h.borrow().print_row(f);
         }
         Ok(())
     }
 }
 
+/// Ordered by key only, so the list can find an entry from just the key
+#[derive(Debug)]
+pub struct MapEntry<K, V> {
+    pub k: K,
+    pub v: V,
+}
+
+impl<K: PartialEq, V> PartialEq for MapEntry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.k == other.k
+    }
+}
+
+impl<K: PartialOrd, V> PartialOrd for MapEntry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.k.partial_cmp(&other.k)
+    }
+}
+
+/// A SkipList of entries with at most one entry per key
+#[derive(Debug)]
+pub struct SkipMap<K: PartialOrd, V, G = RandGen>(SkipList<MapEntry<K, V>, G>);
+
+impl<K: PartialOrd, V> Default for SkipMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: PartialOrd, V> SkipMap<K, V> {
+    pub fn new() -> Self {
+        SkipMap(SkipList::new())
+    }
+}
+
+impl<K: PartialOrd, V, G: Iterator<Item = usize>> SkipMap<K, V, G> {
+    pub fn with_rng(rng: G, p: f64, max_level: usize) -> Self {
+        SkipMap(SkipList::with_rng(rng, p, max_level))
+    }
+
+    pub fn level_histogram(&self) -> Vec<usize> {
+        self.0.level_histogram()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn entry(&self, k: &K) -> Option<Rcc<MapEntry<K, V>>> {
+        let e = self.0.seek(|e| e.k < *k).next()?;
+        if e.borrow().k == *k {
+            Some(e)
+        } else {
+            None
+        }
+    }
+
+    /// Every level shares the entry, so replacing the value in place is enough
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        if let Some(e) = self.entry(&k) {
+            return Some(std::mem::replace(&mut e.borrow_mut().v, v));
+        }
+        self.0.insert(MapEntry { k, v });
+        None
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.entry(k).is_some()
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let e = self.0.remove_by(|e| e.k.partial_cmp(k))?;
+        // The map never hands out its entries, so once unlinked this is the last ref
+        Rc::try_unwrap(e).ok().map(|e| e.into_inner().v)
+    }
+}
+
+impl<K: PartialOrd + Clone, V: Clone, G: Iterator<Item = usize>> SkipMap<K, V, G> {
+    pub fn get(&self, k: &K) -> Option<V> {
+        self.entry(k).map(|e| e.borrow().v.clone())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
+        self.0.iter().map(|e| {
+            let e = e.borrow();
+            (e.k.clone(), e.v.clone())
+        })
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, r: R) -> impl Iterator<Item = (K, V)> {
+        self.0
+            .seek(|e| before_start(r.start_bound(), &e.k))
+            .take_while(move |e| before_end(r.end_bound(), &e.borrow().k))
+            .map(|e| {
+                let e = e.borrow();
+                (e.k.clone(), e.v.clone())
+            })
+    }
+}
+
 fn main() {
     let mut sl = SkipList::new();
     sl.insert(4);
@@ -152,4 +428,151 @@ fn main() {
     sl.insert(9);
 
     println!("{}", sl);
+    println!("len = {}, contains 12 = {}", sl.len(), sl.contains(&12));
+    sl.remove(&7);
+    sl.remove(&2);
+    println!("after removing 7 and 2: {}", sl);
+    println!(
+        "first = {:?}, last = {:?}, 5 = {:?}",
+        sl.first(),
+        sl.last(),
+        sl.get(&5)
+    );
+    let mid: Vec<i32> = sl.range(5..=12).map(|d| *d.borrow()).collect();
+    println!("5..=12 = {:?}", mid);
+
+    let mut sm = SkipMap::new();
+    for (i, w) in "the quick brown fox jumps over the lazy dog"
+        .split(' ')
+        .enumerate()
+    {
+        sm.insert(w, i);
+    }
+    sm.remove(&"fox");
+    println!(
+        "map len {}, empty {}, has dog {}, the = {:?}",
+        sm.len(),
+        sm.is_empty(),
+        sm.contains_key(&"dog"),
+        sm.get(&"the")
+    );
+    println!("{:?}", sm.iter().collect::<Vec<_>>());
+    println!("{:?}", sm.range("d".."p").collect::<Vec<_>>());
+
+    // Fewer levels for lower p, trading longer rows for less memory
+    for p in &[0.5, 0.25, 0.1] {
+        let mut m = SkipMap::with_rng(RandGen::seeded(7), *p, 12);
+        for i in 0..10000 {
+            m.insert(i, ());
+        }
+        println!("p = {}: {:?}", p, m.level_histogram());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Every row in order, and every node above the bottom sits on
+    /// a node below it holding the same data
+    fn check_levels<T: PartialOrd + Debug, G: Iterator<Item = usize>>(sl: &SkipList<T, G>) {
+        for h in &sl.heads {
+            let mut n = h.clone();
+            while let Some(nd) = n {
+                let nb = nd.borrow();
+                if let Some(d) = &nb.down {
+                    assert!(Rc::ptr_eq(&d.borrow().data, &nb.data));
+                }
+                if let Some(r) = &nb.right {
+                    assert!(*nb.data.borrow() <= *r.borrow().data.borrow());
+                }
+                n = nb.right.clone();
+            }
+        }
+        let hist = sl.level_histogram();
+        assert!(hist.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(hist.first().cloned().unwrap_or(0), sl.len());
+    }
+
+    #[test]
+    fn test_list_ops() {
+        let mut sl = SkipList::new();
+        for i in &[5, 3, 9, 1, 7, 3, 8] {
+            sl.insert(*i);
+        }
+        check_levels(&sl);
+        let all: Vec<i32> = sl.iter().map(|d| *d.borrow()).collect();
+        assert_eq!(all, vec![1, 3, 3, 5, 7, 8, 9]);
+        assert!(sl.contains(&7));
+        assert!(!sl.contains(&4));
+        assert_eq!(*sl.first().unwrap().borrow(), 1);
+        assert_eq!(*sl.last().unwrap().borrow(), 9);
+
+        let mid: Vec<i32> = sl.range(3..8).map(|d| *d.borrow()).collect();
+        assert_eq!(mid, vec![3, 3, 5, 7]);
+
+        assert_eq!(*sl.remove(&3).unwrap().borrow(), 3);
+        assert!(sl.contains(&3));
+        assert!(sl.remove(&3).is_some());
+        assert!(sl.remove(&3).is_none());
+        assert_eq!(sl.len(), 5);
+        check_levels(&sl);
+        for i in &[1, 5, 7, 8, 9] {
+            sl.remove(i);
+        }
+        assert!(sl.is_empty());
+        assert!(sl.heads.is_empty());
+        assert!(sl.last().is_none());
+    }
+
+    #[test]
+    fn test_map_against_btreemap() {
+        let mut sm = SkipMap::new();
+        let mut bt = BTreeMap::new();
+        for i in 0..2000u64 {
+            let k = (i * 7919) % 257;
+            if i % 3 == 0 {
+                assert_eq!(sm.remove(&k), bt.remove(&k));
+            } else {
+                assert_eq!(sm.insert(k, i), bt.insert(k, i));
+            }
+        }
+        check_levels(&sm.0);
+        assert_eq!(sm.len(), bt.len());
+        assert_eq!(
+            sm.iter().collect::<Vec<_>>(),
+            bt.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            sm.range(40..=90).collect::<Vec<_>>(),
+            bt.range(40..=90).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        for k in 0..260 {
+            assert_eq!(sm.get(&k), bt.get(&k).cloned());
+        }
+    }
+
+    #[test]
+    fn test_seeded_shape_and_limits() {
+        let build = |seed, p, max| {
+            let mut sl = SkipList::with_rng(RandGen::seeded(seed), p, max);
+            for i in 0..4000 {
+                sl.insert(i);
+            }
+            check_levels(&sl);
+            sl.level_histogram()
+        };
+        assert_eq!(build(3, 0.5, 32), build(3, 0.5, 32));
+
+        let half = build(3, 0.5, 32);
+        assert!(half[1] > 1600 && half[1] < 2400, "{:?}", half);
+        let quarter = build(3, 0.25, 32);
+        assert!(quarter[1] > 700 && quarter[1] < 1300, "{:?}", quarter);
+        assert!(quarter.len() < half.len());
+
+        let capped = build(3, 0.5, 3);
+        assert_eq!(capped.len(), 3);
+        assert_eq!(capped[0], 4000);
+    }
 }
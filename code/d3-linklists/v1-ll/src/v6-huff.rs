@@ -1,114 +1,51 @@
+use ll::huffman::{
+    canonical_codes, code_lengths, compress, decompress, limited_lengths, CanonicalCodec,
+    HuffmanCodec,
+};
 use std::collections::BTreeMap;
 
-#[derive(Debug)]
-pub enum HuffNode {
-    Tree(Box<HuffNode>, Box<HuffNode>),
-    Leaf(char),
-}
-
-pub struct HScore {
-    h: HuffNode,
-    s: i32,
-}
-
-impl HuffNode{
-    pub fn lfirst_print(&self,depth:i32,dir:char){
-        match self{
-            HuffNode::Tree(l,r)=>{
-                l.lfirst_print(depth+1,'/');
-                let mut spc = String::new();
-                for _ in 0..depth{
-                    spc.push('.');
-                }
-                println!("{}{}*",spc,dir);
-                r.lfirst_print(depth+1,'\\');
-            }
-            HuffNode::Leaf(c)=>{
-                let mut spc = String::new();
-                for _ in 0..depth{
-                    spc.push('.');
-                }
-                println!("{}{}{}",spc,dir,c);
-            }
-        }
+pub fn main() {
+    let s = "at an apple app";
+    let codec = HuffmanCodec::new(s.chars());
+    if let Some(t) = codec.tree() {
+        t.lfirst_print(0, '<');
     }
 
-    //could be bool,or num, but char is easier to print and show the sequence
-    pub fn encode_char(&self, c:char)->Option<Vec<char>>{
-        match self{
-            HuffNode::Tree(l,r)=>{
-                if let Some(mut v) = l.encode_char(c){
-                    v.insert(0,'0');
-                    return Some(v);
-                }
-                if let Some(mut v) = r.encode_char(c){
-                    v.insert(0,'1');
-                    return Some(v);
-                }
-                None
-            }
-            HuffNode::Leaf(nc)=>{
-                if c == *nc {
-                    return Some(Vec::new());
-                }
-                None
-            }
-        }
-    }
+    println!("n = {:?}", codec.code('n'));
 
-    pub fn encode_str(&self,s:&str)->Vec<char>{
-        let mut res = Vec::new();
-        for c in s.chars(){
-            let v = self.encode_char(c).unwrap();
-            res.extend(v.into_iter());
-        }
-        res
-    }
-}
+    let enc = codec.encode_str(s).unwrap();
+    println!("{} bytes -> {} bytes = {:?}", s.len(), enc.len(), enc);
+    println!("decoded = {:?}", HuffmanCodec::decode_str(&enc));
 
-pub fn build_tree(s: &str) -> HuffNode {
-    let mut map = BTreeMap::new();
-    for c in s.chars() {
-        let n = *map.get(&c).unwrap_or(&0);
-        map.insert(c, n + 1);
+    // Canonical codes only need the lengths from the tree
+    if let Some(t) = codec.tree() {
+        let lens = code_lengths(t);
+        println!("canonical = {:?}", canonical_codes(&lens));
     }
-    let mut tlist: Vec<HScore> = map
-        .into_iter()
-        .map(|(k, s)| HScore {
-            h: HuffNode::Leaf(k),
-            s,
-        })
-        .collect();
-
-    while tlist.len() > 1 {
-        let last = tlist.len() - 1;
-        for i in 0..tlist.len() - 2 {
-            if tlist[i].s < tlist[last - 1].s {
-                tlist.swap(i, last - 1);
-            }
-            if tlist[last - 1].s < tlist[last].s {
-                tlist.swap(last - 1, last);
-            }
-        }
-        let a_node = tlist.pop().unwrap();
-        let b_node = tlist.pop().unwrap();
-        let nnode = HuffNode::Tree(Box::new(a_node.h), Box::new(b_node.h));
-        tlist.push(HScore {
-            h: nnode,
-            s: a_node.s + b_node.s,
-        });
+    let mut counts = BTreeMap::new();
+    for c in s.chars() {
+        *counts.entry(c).or_insert(0) += 1;
     }
-    tlist.pop().unwrap().h
-}
-
-pub fn main() {
-    let s = "at an apple app" ;
-    let t = build_tree(s);
-    t.lfirst_print(0,'<');
-    //println!("Tree={:?}", t);
-    //
-    println!("n = {:?}" ,t.encode_char('n'));
-
-    println!("ecs = {:?}", t.encode_str(s));
-    //let e = t.encode(s);
+    println!("lengths capped at 3 = {:?}", limited_lengths(&counts, 3));
+    let capped = CanonicalCodec::new(s.chars(), 3).unwrap();
+    let enc = capped.encode(&s.chars().collect::<Vec<_>>()).unwrap();
+    println!(
+        "capped {:?}, 'p' = {:?}, decoded = {:?}",
+        capped.lengths(),
+        capped.code('p'),
+        CanonicalCodec::<char>::decode(&enc).map(|v| v.into_iter().collect::<String>())
+    );
+
+    // Any Read and Write will do, here the module's own source
+    let src = include_bytes!("huffman/mod.rs");
+    let mut packed = Vec::new();
+    let n = compress(&src[..], &mut packed).unwrap();
+    let mut back = Vec::new();
+    decompress(&packed[..], &mut back).unwrap();
+    println!(
+        "huffman/mod.rs: {} bytes -> {} bytes, round trip ok = {}",
+        src.len(),
+        n,
+        back[..] == src[..]
+    );
 }
@@ -0,0 +1,403 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// A balanced tree where every change returns a new map.
+/// Untouched branches are shared between versions through Rc,
+/// so only the path from the root to the change is copied.
+#[derive(Debug)]
+pub struct PersistentMap<K, V>(Option<Rc<PNode<K, V>>>);
+
+#[derive(Debug)]
+pub struct PNode<K, V> {
+    h: i8,
+    k: K,
+    v: V,
+    left: PersistentMap<K, V>,
+    right: PersistentMap<K, V>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Diff<'a, K, V> {
+    Added(&'a K, &'a V),
+    Removed(&'a K, &'a V),
+    Changed(&'a K, &'a V, &'a V),
+}
+
+// Derive would require K:Clone and V:Clone, but we only copy the pointer
+impl<K, V> Clone for PersistentMap<K, V> {
+    fn clone(&self) -> Self {
+        PersistentMap(self.0.clone())
+    }
+}
+
+impl<K, V> Default for PersistentMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone, V: Clone> PNode<K, V> {
+    /// Copy this node with new children, the children themselves are shared
+    fn with_children(&self, left: PersistentMap<K, V>, right: PersistentMap<K, V>) -> Rc<Self> {
+        Rc::new(PNode {
+            h: 1 + std::cmp::max(left.height(), right.height()),
+            k: self.k.clone(),
+            v: self.v.clone(),
+            left,
+            right,
+        })
+    }
+
+    /// Path copying version of BinData::rot_left
+    /// The old node is left alone for any other version still using it.
+    pub fn rot_left(&self) -> Rc<Self> {
+        let res = match self.right.0 {
+            Some(ref res) => res,
+            None => return self.with_children(self.left.clone(), self.right.clone()),
+        };
+        let nleft = self.with_children(self.left.clone(), res.left.clone());
+        res.with_children(PersistentMap(Some(nleft)), res.right.clone())
+    }
+
+    /// Path copying version of BinData::rot_right
+    pub fn rot_right(&self) -> Rc<Self> {
+        let res = match self.left.0 {
+            Some(ref res) => res,
+            None => return self.with_children(self.left.clone(), self.right.clone()),
+        };
+        let nright = self.with_children(res.right.clone(), self.right.clone());
+        res.with_children(res.left.clone(), PersistentMap(Some(nright)))
+    }
+
+    /// Rotate a freshly copied node back into balance
+    fn balance(self: Rc<Self>) -> Rc<Self> {
+        let lh = self.left.height();
+        let rh = self.right.height();
+        if lh - rh > 1 {
+            let l = self.left.0.as_ref().unwrap(); // lh > 1
+            if l.right.height() > l.left.height() {
+                let nl = l.rot_left();
+                return self
+                    .with_children(PersistentMap(Some(nl)), self.right.clone())
+                    .rot_right();
+            }
+            return self.rot_right();
+        }
+        if rh - lh > 1 {
+            let r = self.right.0.as_ref().unwrap(); // rh > 1
+            if r.left.height() > r.right.height() {
+                let nr = r.rot_right();
+                return self
+                    .with_children(self.left.clone(), PersistentMap(Some(nr)))
+                    .rot_left();
+            }
+            return self.rot_left();
+        }
+        self
+    }
+}
+
+impl<K, V> PersistentMap<K, V> {
+    pub fn new() -> Self {
+        PersistentMap(None)
+    }
+
+    pub fn height(&self) -> i8 {
+        match self.0 {
+            Some(ref t) => t.h,
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Do two versions share the same root, if so they must be equal
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut res = Iter { stack: Vec::new() };
+        res.push_left(self);
+        res
+    }
+}
+
+impl<K: Ord, V> PersistentMap<K, V> {
+    pub fn get(&self, k: &K) -> Option<&V> {
+        let mut curr = self;
+        while let Some(ref n) = curr.0 {
+            curr = match k.cmp(&n.k) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return Some(&n.v),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentMap<K, V> {
+    /// Returns a new version with k set to v, self is unchanged
+    pub fn insert(&self, k: K, v: V) -> Self {
+        let n = match self.0 {
+            Some(ref n) => n,
+            None => {
+                return PersistentMap(Some(Rc::new(PNode {
+                    h: 1,
+                    k,
+                    v,
+                    left: PersistentMap::new(),
+                    right: PersistentMap::new(),
+                })));
+            }
+        };
+        let res = match k.cmp(&n.k) {
+            Ordering::Less => n.with_children(n.left.insert(k, v), n.right.clone()),
+            Ordering::Greater => n.with_children(n.left.clone(), n.right.insert(k, v)),
+            Ordering::Equal => Rc::new(PNode {
+                h: n.h,
+                k,
+                v,
+                left: n.left.clone(),
+                right: n.right.clone(),
+            }),
+        };
+        PersistentMap(Some(res.balance()))
+    }
+
+    /// Returns a new version without k, if k is missing the result shares the whole tree
+    pub fn remove(&self, k: &K) -> Self {
+        let n = match self.0 {
+            Some(ref n) => n,
+            None => return PersistentMap::new(),
+        };
+        let res = match k.cmp(&n.k) {
+            Ordering::Less => {
+                let nl = n.left.remove(k);
+                if nl.ptr_eq(&n.left) {
+                    return self.clone();
+                }
+                n.with_children(nl, n.right.clone())
+            }
+            Ordering::Greater => {
+                let nr = n.right.remove(k);
+                if nr.ptr_eq(&n.right) {
+                    return self.clone();
+                }
+                n.with_children(n.left.clone(), nr)
+            }
+            Ordering::Equal => match (&n.left.0, &n.right.0) {
+                (None, _) => return n.right.clone(),
+                (_, None) => return n.left.clone(),
+                (_, Some(_)) => {
+                    // Replace with the smallest node on the right
+                    let (rk, rv, nr) = n.right.pop_first();
+                    Rc::new(PNode {
+                        h: 1 + std::cmp::max(n.left.height(), nr.height()),
+                        k: rk,
+                        v: rv,
+                        left: n.left.clone(),
+                        right: nr,
+                    })
+                }
+            },
+        };
+        PersistentMap(Some(res.balance()))
+    }
+
+    /// panics if empty, only called on a known right child
+    fn pop_first(&self) -> (K, V, Self) {
+        let n = self.0.as_ref().expect("pop_first on empty tree");
+        if n.left.is_empty() {
+            return (n.k.clone(), n.v.clone(), n.right.clone());
+        }
+        let (k, v, nl) = n.left.pop_first();
+        let res = n.with_children(nl, n.right.clone()).balance();
+        (k, v, PersistentMap(Some(res)))
+    }
+}
+
+impl<K: Ord, V: PartialEq> PersistentMap<K, V> {
+    /// Everything needed to turn old into new, in key order
+    pub fn diff<'a>(old: &'a Self, new: &'a Self) -> Vec<Diff<'a, K, V>> {
+        let mut res = Vec::new();
+        if old.ptr_eq(new) {
+            return res;
+        }
+        let mut a_it = old.iter();
+        let mut b_it = new.iter();
+        let mut a_peek = a_it.next();
+        let mut b_peek = b_it.next();
+        loop {
+            match (a_peek, b_peek) {
+                (Some((ak, av)), Some((bk, bv))) => match ak.cmp(bk) {
+                    Ordering::Less => {
+                        res.push(Diff::Removed(ak, av));
+                        a_peek = a_it.next();
+                    }
+                    Ordering::Greater => {
+                        res.push(Diff::Added(bk, bv));
+                        b_peek = b_it.next();
+                    }
+                    Ordering::Equal => {
+                        if av != bv {
+                            res.push(Diff::Changed(ak, av, bv));
+                        }
+                        a_peek = a_it.next();
+                        b_peek = b_it.next();
+                    }
+                },
+                (Some((ak, av)), None) => {
+                    res.push(Diff::Removed(ak, av));
+                    a_peek = a_it.next();
+                }
+                (None, Some((bk, bv))) => {
+                    res.push(Diff::Added(bk, bv));
+                    b_peek = b_it.next();
+                }
+                (None, None) => return res,
+            }
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> PersistentMap<K, V> {
+    pub fn lfirst_print(&self, depth: i32) {
+        if let Some(ref bd) = self.0 {
+            bd.left.lfirst_print(depth + 1);
+            let mut spc = String::new();
+            for _ in 0..depth {
+                spc.push('.');
+            }
+            println!("{}\t   {}{:?}={:?}", bd.h, spc, bd.k, bd.v);
+            bd.right.lfirst_print(depth + 1);
+        }
+    }
+}
+
+/// In order iterator, the stack holds the nodes whose left side is done
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a PNode<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut t: &'a PersistentMap<K, V>) {
+        while let Some(ref n) = t.0 {
+            self.stack.push(n);
+            t = &n.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.stack.pop()?;
+        self.push_left(&n.right);
+        Some((&n.k, &n.v))
+    }
+}
+
+fn main() {
+    let mut versions = vec![PersistentMap::new()];
+    for i in 0..20 {
+        let last = versions.last().unwrap();
+        versions.push(last.insert(i, i * 10));
+    }
+    let last = versions.last().unwrap();
+    let removed = last.remove(&7).insert(3, 99);
+    removed.lfirst_print(0);
+
+    println!("v5 = {:?}", versions[5].iter().collect::<Vec<_>>());
+    println!("v5 has 7 = {}", versions[5].contains_key(&7));
+    println!("len last = {}", last.len());
+    println!("get 3 = {:?}", removed.get(&3));
+    println!("diff = {:?}", PersistentMap::diff(last, &removed));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_balanced<K, V>(t: &PersistentMap<K, V>) -> i8 {
+        match t.0 {
+            Some(ref n) => {
+                let l = check_balanced(&n.left);
+                let r = check_balanced(&n.right);
+                assert!((l - r).abs() <= 1, "unbalanced at height {}", n.h);
+                assert_eq!(n.h, 1 + std::cmp::max(l, r));
+                n.h
+            }
+            None => 0,
+        }
+    }
+
+    #[test]
+    fn test_old_versions_unchanged() {
+        let mut t = PersistentMap::new();
+        for i in 0..100 {
+            t = t.insert(i, i);
+        }
+        let t2 = t.insert(50, 500).remove(&10);
+        assert_eq!(t.get(&50), Some(&50));
+        assert_eq!(t.get(&10), Some(&10));
+        assert_eq!(t2.get(&50), Some(&500));
+        assert_eq!(t2.get(&10), None);
+        assert_eq!(t.len(), 100);
+        assert_eq!(t2.len(), 99);
+        check_balanced(&t);
+        check_balanced(&t2);
+    }
+
+    #[test]
+    fn test_remove_keeps_order_and_balance() {
+        let mut t = PersistentMap::new();
+        for i in 0..200 {
+            t = t.insert((i * 37) % 200, i);
+        }
+        for i in (0..200).filter(|i| i % 3 == 0) {
+            t = t.remove(&i);
+            check_balanced(&t);
+        }
+        let keys: Vec<i32> = t.iter().map(|(k, _)| *k).collect();
+        let expected: Vec<i32> = (0..200).filter(|i| i % 3 != 0).collect();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_remove_missing_shares_tree() {
+        let t = PersistentMap::new().insert(1, "a").insert(2, "b");
+        assert!(t.remove(&5).ptr_eq(&t));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = PersistentMap::new().insert(1, "a").insert(2, "b").insert(3, "c");
+        let b = a.remove(&1).insert(3, "C").insert(4, "d");
+        assert_eq!(
+            PersistentMap::diff(&a, &b),
+            vec![
+                Diff::Removed(&1, &"a"),
+                Diff::Changed(&3, &"c", &"C"),
+                Diff::Added(&4, &"d"),
+            ]
+        );
+        assert!(PersistentMap::diff(&a, &a.clone()).is_empty());
+    }
+}
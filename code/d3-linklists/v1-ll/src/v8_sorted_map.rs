@@ -0,0 +1,95 @@
+mod b_rand;
+mod sorted_map;
+
+use b_rand::RandGen;
+use sorted_map::*;
+use std::time::Instant;
+
+/// Time the same workload on any SortedMap, keys come from next_key
+/// so we can compare random access with sorted or skewed patterns.
+fn bench<M: SortedMap<usize, usize>>(
+    name: &str,
+    mut m: M,
+    n: usize,
+    next_key: &mut dyn FnMut() -> usize,
+) {
+    let start = Instant::now();
+    for i in 0..n {
+        m.insert(next_key(), i);
+    }
+    let t_ins = start.elapsed();
+    let size = m.len();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for _ in 0..n {
+        if m.get(&next_key()).is_some() {
+            found += 1;
+        }
+    }
+    let t_get = start.elapsed();
+
+    let start = Instant::now();
+    let in_range = range_of(&m, &(0..n / 2)).count();
+    let total = m.iter().count();
+    let t_range = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..n {
+        m.remove(&next_key());
+    }
+    let t_rem = start.elapsed();
+
+    println!(
+        "{:>6}: insert {:>10?} get {:>10?} range {:>10?} remove {:>10?} (size {}, found {}, {}/{} in range, emptied {})",
+        name,
+        t_ins,
+        t_get,
+        t_range,
+        t_rem,
+        size,
+        found,
+        in_range,
+        total,
+        m.is_empty(),
+    );
+}
+
+fn run_all(title: &str, n: usize, keys: &dyn Fn() -> Box<dyn FnMut() -> usize>) {
+    println!("-- {} n = {} --", title, n);
+    bench("bst", BstMap::new(), n, &mut keys());
+    bench("avl", AvlMap::new(), n, &mut keys());
+    bench("splay", SplayMap::new(), n, &mut keys());
+    bench("treap", TreapMap::new(3), n, &mut keys());
+}
+
+fn main() {
+    let n = 20000;
+    run_all("random", n, &|| {
+        let mut rg = RandGen::seeded(9);
+        Box::new(move || rg.next().unwrap() % n)
+    });
+
+    // Sorted keys are the worst case for the plain tree, so keep it small
+    let n_sorted = 2000;
+    run_all("sorted", n_sorted, &|| {
+        let mut i = 0;
+        Box::new(move || {
+            i += 1;
+            i % n_sorted
+        })
+    });
+
+    // Most accesses hit a few hot keys, where splaying should shine
+    run_all("skewed", n, &|| {
+        let mut rg = RandGen::seeded(4);
+        Box::new(move || {
+            let r = rg.next().unwrap();
+            if r % 10 < 9 {
+                r % 16
+            } else {
+                r % n
+            }
+        })
+    });
+}
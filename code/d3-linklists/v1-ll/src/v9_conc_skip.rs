@@ -0,0 +1,446 @@
+mod b_rand;
+
+use b_rand::RandGen;
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use std::collections::HashSet;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed};
+use std::sync::Arc;
+use std::thread;
+
+const MAX_LEVEL: usize = 16;
+
+struct Node<K, V> {
+    k: K,
+    v: V,
+    // tag 1 on next[i] marks this node as removed on level i
+    next: Vec<Atomic<Node<K, V>>>,
+    // levels this node is linked on, plus one held by its inserter until it is done linking
+    refs: AtomicUsize,
+}
+
+/// Lock free version of the SkipMap from v5_skip.
+/// Removal first marks a node's next pointers, then any thread that walks past
+/// a marked node unlinks it. Nodes are freed through crossbeam's epochs once
+/// the last level lets go of them, so readers never see freed memory.
+/// Heights come from a RandGen state shared by all threads, so a single
+/// threaded run from the same seed builds the same shape.
+pub struct ConcurrentSkipMap<K, V> {
+    head: Vec<Atomic<Node<K, V>>>,
+    len: AtomicUsize,
+    rng: AtomicUsize,
+}
+
+/// The last node before k on each level, and the one after it
+struct Position<'g, K, V> {
+    preds: [&'g Atomic<Node<K, V>>; MAX_LEVEL],
+    succs: [Shared<'g, Node<K, V>>; MAX_LEVEL],
+}
+
+fn is_key<K: Ord, V>(s: Shared<Node<K, V>>, k: &K) -> bool {
+    // Safety: callers hold the guard s was loaded with
+    unsafe { s.as_ref() }.is_some_and(|n| n.k == *k)
+}
+
+/// Drop one link to n, freeing it once nothing points at it
+unsafe fn release<K, V>(n: Shared<Node<K, V>>, guard: &Guard) {
+    if n.deref().refs.fetch_sub(1, AcqRel) == 1 {
+        guard.defer_destroy(n);
+    }
+}
+
+impl<K, V> Default for ConcurrentSkipMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> ConcurrentSkipMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_seed(1)
+    }
+
+    pub fn with_seed(seed: usize) -> Self {
+        ConcurrentSkipMap {
+            head: (0..MAX_LEVEL).map(|_| Atomic::null()).collect(),
+            len: AtomicUsize::new(0),
+            rng: AtomicUsize::new(seed),
+        }
+    }
+
+    /// Each level above the bottom with chance 1/2
+    fn random_height(&self) -> usize {
+        let step = |s| RandGen::seeded(s).next();
+        let prev = self.rng.fetch_update(Relaxed, Relaxed, step).unwrap();
+        let draw = step(prev).unwrap() % (1 << 16);
+        std::cmp::min(draw.trailing_zeros() as usize + 1, MAX_LEVEL)
+    }
+
+    /// Only exact when no other thread is changing the map
+    pub fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Ord + Send + Sync + 'static, V: Send + Sync + 'static> ConcurrentSkipMap<K, V> {
+    /// Walk down to k, unlinking any marked nodes on the way.
+    /// If another thread changes a pointer under us, start again from the top.
+    fn find<'g>(&'g self, k: &K, guard: &'g Guard) -> Position<'g, K, V> {
+        'retry: loop {
+            let mut pos = Position {
+                preds: [&self.head[0]; MAX_LEVEL],
+                succs: [Shared::null(); MAX_LEVEL],
+            };
+            let mut pred: &'g [Atomic<Node<K, V>>] = &self.head;
+            for level in (0..MAX_LEVEL).rev() {
+                let mut curr = pred[level].load(Acquire, guard);
+                if curr.tag() == 1 {
+                    continue 'retry;
+                }
+                // Safety: the guard keeps every node reached from the list alive
+                while let Some(c) = unsafe { curr.as_ref() } {
+                    let succ = c.next[level].load(Acquire, guard);
+                    if succ.tag() == 1 {
+                        match pred[level].compare_exchange(
+                            curr,
+                            succ.with_tag(0),
+                            AcqRel,
+                            Acquire,
+                            guard,
+                        ) {
+                            Ok(_) => {
+                                unsafe { release(curr, guard) };
+                                curr = succ.with_tag(0);
+                            }
+                            Err(_) => continue 'retry,
+                        }
+                    } else if c.k < *k {
+                        pred = &c.next;
+                        curr = succ;
+                    } else {
+                        break;
+                    }
+                }
+                pos.preds[level] = &pred[level];
+                pos.succs[level] = curr;
+            }
+            return pos;
+        }
+    }
+
+    /// Returns false and leaves the old value if k is already there
+    pub fn insert(&self, k: K, v: V) -> bool {
+        let guard = &epoch::pin();
+        let mut pos = self.find(&k, guard);
+        if is_key(pos.succs[0], &k) {
+            return false;
+        }
+        let height = self.random_height();
+        let node = Owned::new(Node {
+            k,
+            v,
+            next: (0..height).map(|_| Atomic::null()).collect(),
+            refs: AtomicUsize::new(1),
+        })
+        .into_shared(guard);
+        let n = unsafe { node.deref() };
+
+        // Once linked on the bottom level the key is in the map
+        loop {
+            n.next[0].store(pos.succs[0], Relaxed);
+            n.refs.fetch_add(1, Relaxed);
+            if pos.preds[0]
+                .compare_exchange(pos.succs[0], node, AcqRel, Acquire, guard)
+                .is_ok()
+            {
+                break;
+            }
+            n.refs.fetch_sub(1, Relaxed);
+            pos = self.find(&n.k, guard);
+            if is_key(pos.succs[0], &n.k) {
+                // Another insert of the same key got there first, no one else has seen node
+                unsafe { drop(node.into_owned()) };
+                return false;
+            }
+        }
+        self.len.fetch_add(1, Relaxed);
+
+        // The upper levels only speed up searches, stop if a remove has started on us
+        'levels: for level in 1..height {
+            loop {
+                let old = n.next[level].load(Acquire, guard);
+                if old.tag() == 1
+                    || n.next[level]
+                        .compare_exchange(old, pos.succs[level], AcqRel, Acquire, guard)
+                        .is_err()
+                {
+                    break 'levels;
+                }
+                n.refs.fetch_add(1, Relaxed);
+                if pos.preds[level]
+                    .compare_exchange(pos.succs[level], node, AcqRel, Acquire, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                n.refs.fetch_sub(1, Relaxed);
+                pos = self.find(&n.k, guard);
+            }
+        }
+        // A remove may have tidied up before our last link went in
+        if n.next[0].load(Acquire, guard).tag() == 1 {
+            self.find(&n.k, guard);
+        }
+        unsafe { release(node, guard) };
+        true
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        let guard = &epoch::pin();
+        is_key(self.find(k, guard).succs[0], k)
+    }
+}
+
+impl<K: Ord + Send + Sync + 'static, V: Clone + Send + Sync + 'static> ConcurrentSkipMap<K, V> {
+    pub fn get(&self, k: &K) -> Option<V> {
+        let guard = &epoch::pin();
+        let pos = self.find(k, guard);
+        unsafe { pos.succs[0].as_ref() }
+            .filter(|n| n.k == *k)
+            .map(|n| n.v.clone())
+    }
+
+    pub fn remove(&self, k: &K) -> Option<V> {
+        let guard = &epoch::pin();
+        let pos = self.find(k, guard);
+        let n = unsafe { pos.succs[0].as_ref() }.filter(|n| n.k == *k)?;
+        for level in (1..n.next.len()).rev() {
+            n.next[level].fetch_or(1, AcqRel, guard);
+        }
+        // Whoever marks the bottom level owns the remove
+        if n.next[0].fetch_or(1, AcqRel, guard).tag() == 1 {
+            return None;
+        }
+        self.len.fetch_sub(1, Relaxed);
+        let res = n.v.clone();
+        self.find(k, guard);
+        Some(res)
+    }
+}
+
+impl<K, V> Drop for ConcurrentSkipMap<K, V> {
+    fn drop(&mut self) {
+        // No other threads can see the map now. A node may still be linked
+        // on several levels, so collect them all before freeing each once.
+        let guard = unsafe { epoch::unprotected() };
+        let mut nodes = HashSet::new();
+        for (level, h) in self.head.iter().enumerate() {
+            let mut curr = h.load(Relaxed, guard);
+            while let Some(c) = unsafe { curr.with_tag(0).as_ref() } {
+                nodes.insert(curr.with_tag(0).as_raw());
+                curr = c.next[level].load(Relaxed, guard);
+            }
+        }
+        for p in nodes {
+            unsafe { drop(Owned::from_raw(p as *mut Node<K, V>)) };
+        }
+    }
+}
+
+fn main() {
+    let map = Arc::new(ConcurrentSkipMap::new());
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let map = map.clone();
+            thread::spawn(move || {
+                let mut rg = RandGen::seeded(t + 1);
+                let mut added = 0;
+                for _ in 0..10000 {
+                    let k = rg.next().unwrap() % 5000;
+                    if map.insert(k, t) {
+                        added += 1;
+                    } else if map.remove(&k).is_some() {
+                        added -= 1;
+                    }
+                }
+                added
+            })
+        })
+        .collect();
+    let total: i32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    println!(
+        "threads added {} in all, map holds {} (empty {})",
+        total,
+        map.len(),
+        map.is_empty()
+    );
+    println!(
+        "holds 42 = {}, 42 -> {:?}",
+        map.contains_key(&42),
+        map.get(&42)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    const THREADS: usize = 8;
+
+    fn is_send_sync<T: Send + Sync>() {}
+
+    /// How many levels each key is linked on, in key order
+    fn heights<K, V>(m: &ConcurrentSkipMap<K, V>) -> Vec<usize> {
+        let guard = &epoch::pin();
+        let mut res = Vec::new();
+        let mut curr = m.head[0].load(Acquire, guard);
+        while let Some(c) = unsafe { curr.with_tag(0).as_ref() } {
+            res.push(c.next.len());
+            curr = c.next[0].load(Acquire, guard);
+        }
+        res
+    }
+
+    #[test]
+    fn test_threads_match_mutex_btreemap() {
+        is_send_sync::<ConcurrentSkipMap<usize, usize>>();
+        let map = Arc::new(ConcurrentSkipMap::new());
+        let check = Arc::new(Mutex::new(BTreeMap::new()));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (map, check) = (map.clone(), check.clone());
+                thread::spawn(move || {
+                    let mut rg = RandGen::seeded(t + 1);
+                    for _ in 0..5000 {
+                        let r = rg.next().unwrap();
+                        // Threads share the list but each owns the keys equal to t mod THREADS,
+                        // so every result is known from that key's own history
+                        let k = (r / 7 % 300) * THREADS + t;
+                        let mut c = check.lock().unwrap();
+                        match r % 3 {
+                            0 => assert_eq!(map.remove(&k), c.remove(&k)),
+                            1 => assert_eq!(map.get(&k), c.get(&k).cloned()),
+                            _ => {
+                                let fresh = !c.contains_key(&k);
+                                if fresh {
+                                    c.insert(k, r);
+                                }
+                                drop(c);
+                                assert_eq!(map.insert(k, r), fresh);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let c = check.lock().unwrap();
+        assert_eq!(map.len(), c.len());
+        for k in 0..300 * THREADS {
+            assert_eq!(map.get(&k), c.get(&k).cloned());
+        }
+    }
+
+    #[test]
+    fn test_contended_keys() {
+        let map = Arc::new(ConcurrentSkipMap::new());
+        let run = |f: fn(&ConcurrentSkipMap<usize, usize>, usize) -> bool| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let map = map.clone();
+                    thread::spawn(move || (0..1000).filter(|k| f(&map, *k)).count())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .sum::<usize>()
+        };
+        // Every thread tries every key, exactly one of them may win each
+        assert_eq!(run(|m, k| m.insert(k, k)), 1000);
+        assert_eq!(map.len(), 1000);
+        assert_eq!(run(|m, k| m.remove(&k) == Some(k)), 1000);
+        assert!(map.is_empty());
+        assert!(!map.contains_key(&5));
+    }
+
+    #[test]
+    fn test_racing_shared_keys() {
+        const KEYS: usize = 64;
+        let map = Arc::new(ConcurrentSkipMap::with_seed(3));
+        // No lock around the map, every thread works the same few keys,
+        // counting its own wins to check the totals afterwards
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    let mut rg = RandGen::seeded(t + 11);
+                    let mut wins = vec![(0i64, 0i64); KEYS];
+                    for _ in 0..20000 {
+                        let r = rg.next().unwrap();
+                        let k = r / 3 % KEYS;
+                        match r % 3 {
+                            0 => {
+                                if let Some(v) = map.remove(&k) {
+                                    assert_eq!(v % KEYS, k);
+                                    wins[k].1 += 1;
+                                }
+                            }
+                            1 => {
+                                if let Some(v) = map.get(&k) {
+                                    assert_eq!(v % KEYS, k);
+                                }
+                            }
+                            _ => {
+                                if map.insert(k, r / 3) {
+                                    wins[k].0 += 1;
+                                }
+                            }
+                        }
+                    }
+                    wins
+                })
+            })
+            .collect();
+        let mut totals = vec![(0, 0); KEYS];
+        for h in handles {
+            for (k, (ins, rem)) in h.join().unwrap().into_iter().enumerate() {
+                totals[k].0 += ins;
+                totals[k].1 += rem;
+            }
+        }
+        let mut survivors = 0;
+        for (k, (ins, rem)) in totals.into_iter().enumerate() {
+            // each insert is taken out by at most one remove
+            let left = ins - rem;
+            assert!(left == 0 || left == 1, "key {} in {} out {}", k, ins, rem);
+            assert_eq!(map.contains_key(&k), left == 1);
+            survivors += left as usize;
+        }
+        assert_eq!(map.len(), survivors);
+    }
+
+    #[test]
+    fn test_seeded_heights() {
+        let build = |seed| {
+            let m = ConcurrentSkipMap::with_seed(seed);
+            for k in 0..200 {
+                m.insert(k, ());
+            }
+            heights(&m)
+        };
+        assert_eq!(build(5), build(5));
+        assert_ne!(build(5), build(6));
+        let h = build(5);
+        assert_eq!(h.len(), 200);
+        assert!(h.iter().any(|x| *x > 1) && h.iter().all(|x| *x <= MAX_LEVEL));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::dijkstra::Visit;
+use crate::graph::{CycleErr, Graph, Weighted};
+use crate::route::Route;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Shortest distances between every pair of nodes, and the first step of each path
+#[derive(Debug)]
+pub struct DistMatrix<ID: Hash + Eq> {
+    ids: Vec<ID>,
+    index: HashMap<ID, usize>,
+    dist: Vec<Vec<Option<i32>>>,
+    //next[a][b] is the node after a on the way to b
+    next: Vec<Vec<Option<usize>>>,
+}
+
+impl<ID: Clone + Hash + Eq> DistMatrix<ID> {
+    pub fn ids(&self) -> &[ID] {
+        &self.ids
+    }
+
+    pub fn index(&self, id: &ID) -> Option<usize> {
+        self.index.get(id).cloned()
+    }
+
+    pub fn dist(&self, from: &ID, to: &ID) -> Option<i32> {
+        self.dist[self.index(from)?][self.index(to)?]
+    }
+
+    pub fn next_hop(&self, from: &ID, to: &ID) -> Option<&ID> {
+        let n = self.next[self.index(from)?][self.index(to)?]?;
+        Some(&self.ids[n])
+    }
+
+    /// Nodes from from to to, both included
+    pub fn path(&self, from: &ID, to: &ID) -> Option<Vec<ID>> {
+        let (mut a, b) = (self.index(from)?, self.index(to)?);
+        self.dist[a][b]?;
+        let mut res = vec![from.clone()];
+        while a != b {
+            a = self.next[a][b]?;
+            res.push(self.ids[a].clone());
+        }
+        Some(res)
+    }
+
+    /// Continues a route to to along the shortest path
+    pub fn extend(&self, mut route: Rc<Route<ID>>, to: &ID) -> Option<Rc<Route<ID>>> {
+        let path = self.path(&route.pos, to)?;
+        for w in path.windows(2) {
+            let len = route.len + self.dist(&w[0], &w[1])?;
+            route = Rc::new(Route {
+                pos: w[1].clone(),
+                len,
+                path: Some(route),
+            });
+        }
+        Some(route)
+    }
+
+    /// A route visiting each of path in order, skipping any already passed on the way,
+    /// ending at the last whatever
+    pub fn route(&self, path: &[ID]) -> Option<Rc<Route<ID>>> {
+        let (last, mid) = path.split_last()?;
+        let mut route = Route::start_rc(mid.first()?.clone());
+        for pos in &mid[1..] {
+            if !route.contains(pos) {
+                route = self.extend(route, pos)?;
+            }
+        }
+        self.extend(route, last)
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    fn empty_matrix(&self) -> DistMatrix<ID> {
+        let ids: Vec<ID> = self.data.keys().cloned().collect();
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.clone(), i))
+            .collect();
+        let n = ids.len();
+        DistMatrix {
+            ids,
+            index,
+            dist: vec![vec![None; n]; n],
+            next: vec![vec![None; n]; n],
+        }
+    }
+
+    /// Walkable edges as (from, to, weight) indices into m.ids
+    fn index_arcs(&self, m: &DistMatrix<ID>) -> Vec<(usize, usize, i32)> {
+        self.arcs()
+            .into_iter()
+            .map(|(_, f, t, w)| (m.index[f], m.index[t], w))
+            .collect()
+    }
+
+    /// Bellman-Ford names the cycle once we know there is one
+    fn negative_cycle(&self) -> CycleErr<ID> {
+        for id in self.data.keys() {
+            if let Err(e) = self.bellman_ford(id) {
+                return e;
+            }
+        }
+        unreachable!("negative cycle found but not found again")
+    }
+
+    /// O(V³), best when most pairs of nodes have an edge
+    pub fn floyd_warshall(&self) -> Result<DistMatrix<ID>, CycleErr<ID>> {
+        let mut m = self.empty_matrix();
+        let n = m.ids.len();
+        for i in 0..n {
+            m.dist[i][i] = Some(0);
+            m.next[i][i] = Some(i);
+        }
+        for (f, t, w) in self.index_arcs(&m) {
+            if m.dist[f][t].is_none_or(|d| w < d) {
+                m.dist[f][t] = Some(w);
+                m.next[f][t] = Some(t);
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                let ik = match m.dist[i][k] {
+                    Some(d) => d,
+                    None => continue,
+                };
+                for j in 0..n {
+                    if let Some(kj) = m.dist[k][j] {
+                        if m.dist[i][j].is_none_or(|d| ik + kj < d) {
+                            m.dist[i][j] = Some(ik + kj);
+                            m.next[i][j] = m.next[i][k];
+                        }
+                    }
+                }
+            }
+            //past a negative cycle the sums only fall, stop before they overflow
+            if (0..n).any(|i| m.dist[i][i] < Some(0)) {
+                return Err(self.negative_cycle());
+            }
+        }
+        Ok(m)
+    }
+
+    /// Reweights so every edge is 0 or more, then runs Dijkstra from each node.
+    /// O(V·E log V), best when edges are few
+    pub fn johnson(&self) -> Result<DistMatrix<ID>, CycleErr<ID>> {
+        let mut m = self.empty_matrix();
+        let n = m.ids.len();
+        let arcs = self.index_arcs(&m);
+        // Bellman-Ford from an imagined node with a 0 edge to each of ours
+        let mut h = vec![0; n];
+        for round in 0..=n {
+            let mut changed = false;
+            for (f, t, w) in &arcs {
+                if h[*f] + w < h[*t] {
+                    h[*t] = h[*f] + w;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+            if round == n {
+                return Err(self.negative_cycle());
+            }
+        }
+        let mut adj = vec![Vec::new(); n];
+        for (f, t, w) in arcs {
+            adj[f].push((t, w + h[f] - h[t]));
+        }
+        for s in 0..n {
+            let mut dist: Vec<Option<i32>> = vec![None; n];
+            let mut heap = BinaryHeap::new();
+            dist[s] = Some(0);
+            m.next[s][s] = Some(s);
+            heap.push(Visit { dist: 0, pos: s });
+            while let Some(Visit { dist: d, pos }) = heap.pop() {
+                if dist[pos] < Some(d) {
+                    continue;
+                }
+                m.dist[s][pos] = Some(d - h[s] + h[pos]);
+                for (t, w) in &adj[pos] {
+                    if dist[*t].is_some_and(|best| best <= d + w) {
+                        continue;
+                    }
+                    dist[*t] = Some(d + w);
+                    //the first hop is passed down from whoever reached us
+                    m.next[s][*t] = if pos == s { Some(*t) } else { m.next[s][pos] };
+                    heap.push(Visit {
+                        dist: d + w,
+                        pos: *t,
+                    });
+                }
+            }
+        }
+        Ok(m)
+    }
+
+    /// Floyd-Warshall if the graph is dense, Johnson if not
+    pub fn all_pairs_shortest_paths(&self) -> Result<DistMatrix<ID>, CycleErr<ID>> {
+        let v = self.node_count().max(2);
+        let log_v = (usize::BITS - v.leading_zeros()) as usize;
+        if self.edge_count() * log_v >= v * v {
+            self.floyd_warshall()
+        } else {
+            self.johnson()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{build, CycleErr, GraphKind};
+
+    #[test]
+    fn test_floyd_johnson_agree() {
+        let edges = [
+            (0, 0, 1, 3),
+            (1, 0, 2, 8),
+            (2, 1, 3, 1),
+            (3, 1, 4, -4),
+            (4, 2, 1, 4),
+            (5, 3, 0, 2),
+            (6, 3, 2, -5),
+            (7, 4, 3, 6),
+        ];
+        let g = build(GraphKind::Directed, 0..6, &edges);
+        let (fw, jo) = (g.floyd_warshall().unwrap(), g.johnson().unwrap());
+        for a in 0..6 {
+            let bf = g.bellman_ford(&a).unwrap();
+            for b in 0..6 {
+                assert_eq!(fw.dist(&a, &b), bf.dist(&b), "{} {}", a, b);
+                assert_eq!(jo.dist(&a, &b), bf.dist(&b), "{} {}", a, b);
+                assert_eq!(fw.path(&a, &b).is_some(), jo.path(&a, &b).is_some());
+            }
+        }
+        assert_eq!(fw.dist(&0, &2), Some(-1));
+        assert_eq!(fw.path(&0, &2), Some(vec![0, 1, 3, 2]));
+        assert_eq!(jo.path(&0, &2), Some(vec![0, 1, 3, 2]));
+        assert_eq!(jo.next_hop(&3, &4), Some(&2));
+        assert_eq!(fw.dist(&5, &0), None);
+        assert_eq!(fw.ids().len(), 6);
+
+        let r = jo.route(&[0, 2, 4]).unwrap();
+        assert_eq!(r.len, -1);
+    }
+
+    #[test]
+    fn test_negative_cycle() {
+        let g = build(
+            GraphKind::Directed,
+            0..6,
+            &[(0, 0, 1, 1), (1, 1, 2, -3), (2, 2, 0, 1), (3, 3, 4, 1)],
+        );
+        for r in [
+            g.floyd_warshall(),
+            g.johnson(),
+            g.all_pairs_shortest_paths(),
+        ] {
+            match r {
+                Err(CycleErr::NegativeCycle(c)) => assert_eq!(c.len(), 3),
+                r => panic!("expected cycle, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dense_negative_cycle() {
+        // every pair has an edge, so all_pairs picks Floyd-Warshall
+        let mut edges = Vec::new();
+        for a in 0..20 {
+            for b in 0..20 {
+                if a != b {
+                    edges.push((a * 20 + b, a, b, -1000));
+                }
+            }
+        }
+        let g = build(GraphKind::Directed, 0..20, &edges);
+        for r in [g.floyd_warshall(), g.all_pairs_shortest_paths()] {
+            match r {
+                Err(CycleErr::NegativeCycle(c)) => assert!(c.len() >= 2),
+                r => panic!("expected cycle, got {:?}", r.map(|m| m.ids().len())),
+            }
+        }
+        assert!(g.tsp().is_none());
+        assert!(g.greedy_salesman(0).is_none());
+        assert!(g.iter_salesman(0).is_none());
+        assert!(g.mst_salesman(0).is_none());
+    }
+
+    #[test]
+    fn test_salesman_cached() {
+        let g = build(
+            GraphKind::Undirected,
+            0..6,
+            &[
+                (0, 0, 1, 2),
+                (1, 1, 2, 2),
+                (2, 2, 3, 2),
+                (3, 3, 4, 2),
+                (4, 4, 5, 2),
+                (5, 5, 0, 2),
+                (6, 0, 3, 1),
+            ],
+        );
+        for r in [g.greedy_salesman(0), g.iter_salesman(0)] {
+            let r = r.unwrap();
+            assert_eq!(r.pos, 0);
+            assert!((0..6).all(|x| r.contains(&x)));
+        }
+        // 0 3 2, then 2 3 4, then back across 3
+        let r = g.complete_path(&[0, 2, 4, 0]).unwrap();
+        assert_eq!(r.len, 10);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::dijkstra::Visit;
+use crate::graph::{Graph, Weighted};
+use crate::route::Route;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// True if an edge below 0 can be reached from from,
+    /// which searches that take the nearest node first can't handle
+    pub(crate) fn negative_reachable(&self, from: &ID) -> bool {
+        self.reachable(from)
+            .iter()
+            .any(|n| self.out_edges(n).any(|(_, e, _)| e.weight() < 0))
+    }
+
+    /// Like shortest_path, but nodes are tried in order of distance so far plus
+    /// heuristic's guess at what is left, so the search heads towards to.
+    /// The guess must never be more than the real distance, or the route may not be shortest.
+    /// None if to can't be reached, or a negative weight can
+    pub fn astar<H: Fn(&ID) -> i32>(
+        &self,
+        from: ID,
+        to: ID,
+        heuristic: H,
+    ) -> Option<Rc<Route<ID>>> {
+        if self.negative_reachable(&from) {
+            return None;
+        }
+        let mut best = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        best.insert(from.clone(), 0);
+        heap.push(Visit {
+            dist: heuristic(&from),
+            pos: Route::start_rc(from),
+        });
+        while let Some(Visit { pos: route, .. }) = heap.pop() {
+            if route.pos == to {
+                return Some(route);
+            }
+            //a guess that is only admissible can find a node again by a shorter way,
+            //so nodes are reopened and only outdated heap entries skipped
+            if best[&route.pos] < route.len {
+                continue;
+            }
+            for (_, e, npos) in self.out_edges(&route.pos) {
+                let nlen = route.len + e.weight();
+                if best.get(npos).is_some_and(|b| *b <= nlen) {
+                    continue;
+                }
+                best.insert(npos.clone(), nlen);
+                heap.push(Visit {
+                    dist: nlen + heuristic(npos),
+                    pos: Rc::new(Route {
+                        pos: npos.clone(),
+                        len: nlen,
+                        path: Some(route.clone()),
+                    }),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphKind};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let mut g = Graph::new(GraphKind::Directed);
+        for x in 0..6 {
+            g.add_node(x, ()).unwrap();
+        }
+        for (e, a, b, w) in &[
+            (0, 0, 1, 7),
+            (1, 0, 2, 9),
+            (2, 0, 5, 14),
+            (3, 1, 2, 10),
+            (4, 1, 3, 15),
+            (5, 2, 3, 11),
+            (6, 2, 5, 2),
+            (7, 3, 4, 6),
+            (8, 5, 4, 9),
+        ] {
+            g.add_edge(*e, *a, *b, *w).unwrap();
+        }
+        let r = g.astar(0, 4, |_| 0).unwrap();
+        assert_eq!(r.len, 20);
+        assert_eq!(r.len, g.dijkstra(&0).unwrap().dist(&4).unwrap());
+        assert!(r.contains(&5) && r.contains(&2));
+        assert!(g.astar(4, 0, |_| 0).is_none());
+    }
+
+    #[test]
+    fn test_astar_inconsistent_heuristic() {
+        // 0->2 is found first, as 1 looks far, then 1 finds a shorter way into 2
+        let mut g = Graph::new(GraphKind::Directed);
+        for x in 0..4 {
+            g.add_node(x, ()).unwrap();
+        }
+        for (e, a, b, w) in &[(0, 0, 1, 1), (1, 0, 2, 3), (2, 1, 2, 1), (3, 2, 3, 3)] {
+            g.add_edge(*e, *a, *b, *w).unwrap();
+        }
+        let r = g.astar(0, 3, |n| if *n == 1 { 4 } else { 0 }).unwrap();
+        assert_eq!(r.len, 5);
+        assert!(r.contains(&1));
+
+        g.add_edge(4, 1, 0, -1).unwrap();
+        assert!(g.astar(0, 3, |_| 0).is_none());
+
+        // any guess up to the real distance left still gives the shortest length
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..300 {
+            let mut g = Graph::new(GraphKind::Directed);
+            for x in 0..8 {
+                g.add_node(x, ()).unwrap();
+            }
+            for e in 0..16 {
+                let (a, b) = (rng.gen_range(0, 8), rng.gen_range(0, 8));
+                g.add_edge(e, a, b, rng.gen_range(0, 10)).unwrap();
+            }
+            let guess: HashMap<i32, i32> = (0..8)
+                .map(|n| {
+                    let left = g.dijkstra(&n).unwrap().dist(&7).unwrap_or(0);
+                    (n, rng.gen_range(0, left + 1))
+                })
+                .collect();
+            let r = g.astar(0, 7, |n| guess[n]).map(|r| r.len);
+            assert_eq!(r, g.dijkstra(&0).unwrap().dist(&7));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::dijkstra::ShortestPathTree;
+use crate::graph::{CycleErr, Graph, GraphErr, GraphKind, Weighted};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Follows prev back from start, returning the loop it ends in if it does not reach the source
+fn find_cycle<ID: Clone + Hash + Eq, EID>(
+    prev: &HashMap<ID, (EID, ID)>,
+    start: &ID,
+) -> Option<Vec<ID>> {
+    let mut seen = HashSet::new();
+    let mut pos = start;
+    while seen.insert(pos.clone()) {
+        pos = &prev.get(pos)?.1;
+    }
+    // pos is on the loop, walk it once more to list it
+    let mut res = vec![pos.clone()];
+    let mut c = &prev[pos].1;
+    while c != pos {
+        res.push(c.clone());
+        c = &prev[c].1;
+    }
+    res.reverse();
+    Some(res)
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// (edge id, from, to, weight) for every way an edge can be walked
+    pub(crate) fn arcs(&self) -> Vec<(&EID, &ID, &ID, i32)> {
+        let mut res = Vec::new();
+        for (eid, (e, from, to)) in &self.edges {
+            res.push((eid, from, to, e.weight()));
+            if self.kind() == GraphKind::Undirected && from != to {
+                res.push((eid, to, from, e.weight()));
+            }
+        }
+        res
+    }
+
+    /// Shortest paths allowing negative weights, O(V·E).
+    /// Fails naming a negative cycle if one can be reached from source
+    pub fn bellman_ford(&self, source: &ID) -> Result<ShortestPathTree<ID, EID>, CycleErr<ID>> {
+        if !self.has_node(source) {
+            return Err(GraphErr::MissingNode.into());
+        }
+        let arcs = self.arcs();
+        let mut dist: HashMap<ID, i32> = HashMap::new();
+        let mut prev: HashMap<ID, (EID, ID)> = HashMap::new();
+        dist.insert(source.clone(), 0);
+        // V-1 rounds settle every simple path, a change in round V means a cycle
+        for round in 0..self.node_count() {
+            let mut changed = None;
+            for (eid, from, to, w) in &arcs {
+                let d = match dist.get(*from) {
+                    Some(d) => d + w,
+                    None => continue,
+                };
+                if dist.get(*to).is_some_and(|best| *best <= d) {
+                    continue;
+                }
+                dist.insert((*to).clone(), d);
+                prev.insert((*to).clone(), ((*eid).clone(), (*from).clone()));
+                changed = Some(*to);
+            }
+            match changed {
+                None => break,
+                Some(to) if round + 1 == self.node_count() => {
+                    let cycle = find_cycle(&prev, to).expect("round V change is on a cycle");
+                    return Err(CycleErr::NegativeCycle(cycle));
+                }
+                _ => {}
+            }
+        }
+        Ok(ShortestPathTree::new(source.clone(), dist, prev))
+    }
+
+    /// Bellman-Ford that only rechecks nodes whose distance just fell,
+    /// usually much faster, same worst case
+    pub fn spfa(&self, source: &ID) -> Result<ShortestPathTree<ID, EID>, CycleErr<ID>> {
+        if !self.has_node(source) {
+            return Err(GraphErr::MissingNode.into());
+        }
+        let n = self.node_count();
+        let mut dist: HashMap<ID, i32> = HashMap::new();
+        let mut prev: HashMap<ID, (EID, ID)> = HashMap::new();
+        //edges on the best path so far, n or more means it repeats a node
+        let mut steps: HashMap<ID, usize> = HashMap::new();
+        let mut queued = HashSet::new();
+        let mut queue = VecDeque::new();
+        dist.insert(source.clone(), 0);
+        steps.insert(source.clone(), 0);
+        queue.push_back(source.clone());
+        queued.insert(source.clone());
+        while let Some(pos) = queue.pop_front() {
+            queued.remove(&pos);
+            let (d, st) = (dist[&pos], steps[&pos]);
+            for (eid, e, to) in self.out_edges(&pos) {
+                let nd = d + e.weight();
+                if dist.get(to).is_some_and(|best| *best <= nd) {
+                    continue;
+                }
+                dist.insert(to.clone(), nd);
+                prev.insert(to.clone(), (eid.clone(), pos.clone()));
+                steps.insert(to.clone(), st + 1);
+                if st + 1 >= n {
+                    // the loop may since have moved off to's own chain
+                    let cycle = find_cycle(&prev, to)
+                        .or_else(|| prev.keys().find_map(|k| find_cycle(&prev, k)))
+                        .expect("a path of n steps repeats a node");
+                    return Err(CycleErr::NegativeCycle(cycle));
+                }
+                if queued.insert(to.clone()) {
+                    queue.push_back(to.clone());
+                }
+            }
+        }
+        Ok(ShortestPathTree::new(source.clone(), dist, prev))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{build, CycleErr, Graph, GraphErr, GraphKind};
+
+    fn cycle_weight(g: &Graph<(), i32, char>, c: &[char]) -> i32 {
+        let mut res = 0;
+        for i in 0..c.len() {
+            let next = c[(i + 1) % c.len()];
+            res += g
+                .out_edges(&c[i])
+                .filter(|(_, _, t)| **t == next)
+                .map(|(_, w, _)| *w)
+                .min()
+                .unwrap();
+        }
+        res
+    }
+
+    #[test]
+    fn test_negative_weights() {
+        let g = build(
+            GraphKind::Directed,
+            "ABCDE".chars(),
+            &[
+                ('a', 'A', 'B', 4),
+                ('b', 'A', 'C', 2),
+                ('c', 'C', 'B', -3),
+                ('d', 'B', 'D', 2),
+                ('e', 'D', 'E', -1),
+                ('f', 'C', 'E', 5),
+            ],
+        );
+        assert_eq!(g.dijkstra(&'A').err(), Some(GraphErr::NegativeWeight));
+        for t in &[g.bellman_ford(&'A').unwrap(), g.spfa(&'A').unwrap()] {
+            assert_eq!(t.dist(&'B'), Some(-1));
+            assert_eq!(t.dist(&'E'), Some(0));
+            assert_eq!(t.path_to(&'E'), Some(vec!['A', 'C', 'B', 'D', 'E']));
+        }
+        assert_eq!(g.spfa(&'E').unwrap().dist(&'A'), None);
+        assert_eq!(
+            g.bellman_ford(&'Z').err(),
+            Some(CycleErr::Graph(GraphErr::MissingNode))
+        );
+    }
+
+    #[test]
+    fn test_negative_cycle() {
+        let g = build(
+            GraphKind::Directed,
+            "ABCDE".chars(),
+            &[
+                ('a', 'A', 'B', 1),
+                ('b', 'B', 'C', 2),
+                ('c', 'C', 'D', -4),
+                ('d', 'D', 'B', 1),
+                ('e', 'D', 'E', 1),
+            ],
+        );
+        for r in [g.bellman_ford(&'A'), g.spfa(&'A')] {
+            match r {
+                Err(CycleErr::NegativeCycle(c)) => {
+                    let mut s = c.clone();
+                    s.sort();
+                    assert_eq!(s, vec!['B', 'C', 'D']);
+                    assert_eq!(cycle_weight(&g, &c), -1);
+                }
+                r => panic!("expected cycle, got {:?}", r),
+            }
+        }
+        // unreachable from E, so E is fine
+        assert_eq!(g.bellman_ford(&'E').unwrap().dist(&'E'), Some(0));
+
+        // walking an undirected negative edge there and back is a cycle too
+        let u = build(
+            GraphKind::Undirected,
+            "ABCDE".chars(),
+            &[('a', 'A', 'B', 3), ('b', 'B', 'C', -1)],
+        );
+        match u.spfa(&'A') {
+            Err(CycleErr::NegativeCycle(c)) => assert_eq!(c.len(), 2),
+            r => panic!("expected cycle, got {:?}", r),
+        }
+    }
+}
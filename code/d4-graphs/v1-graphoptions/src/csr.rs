@@ -0,0 +1,357 @@
+//! Compressed sparse row graphs, for big graphs that are built once then only read.
+//! Ids are mapped to dense indices, and the arcs leaving each node sit side by side
+//! in flat arrays, so a walk touches a few Vecs rather than a HashMap per step.
+use crate::graph::{Graph, GraphErr, GraphKind, Weighted};
+use crate::options::{EdgeListGraph, MapGraph, MapPGraph, MapRcGraph, RccGraph};
+use crate::traverse::{Traverse, Walk};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct CsrGraph<ID: Hash + Eq> {
+    kind: GraphKind,
+    ids: Vec<ID>,
+    index: HashMap<ID, usize>,
+    //arcs leaving node i are start[i]..start[i + 1]
+    start: Vec<usize>,
+    to: Vec<usize>,
+    weight: Vec<i32>,
+    //an undirected edge is two arcs sharing one edge number
+    edge: Vec<usize>,
+}
+
+impl<ID: Clone + Hash + Eq> CsrGraph<ID> {
+    /// Edges are numbered from 0 in the order given, which is their edge id
+    pub fn new<N, I>(kind: GraphKind, nodes: N, edges: I) -> Result<Self, GraphErr>
+    where
+        N: IntoIterator<Item = ID>,
+        I: IntoIterator<Item = (ID, ID, i32)>,
+    {
+        let mut index = HashMap::new();
+        let mut ids = Vec::new();
+        for id in nodes {
+            if index.insert(id.clone(), ids.len()).is_some() {
+                return Err(GraphErr::DuplicateNode);
+            }
+            ids.push(id);
+        }
+        let mut arcs = Vec::new();
+        for (from, to, w) in edges {
+            let f = *index.get(&from).ok_or(GraphErr::MissingNode)?;
+            let t = *index.get(&to).ok_or(GraphErr::MissingNode)?;
+            arcs.push((f, t, w));
+        }
+        Ok(Self::build(kind, ids, index, &arcs))
+    }
+
+    /// Counting sort of the arcs by where they leave from
+    fn build(
+        kind: GraphKind,
+        ids: Vec<ID>,
+        index: HashMap<ID, usize>,
+        edges: &[(usize, usize, i32)],
+    ) -> Self {
+        let mut arcs = Vec::new();
+        for (e, &(f, t, w)) in edges.iter().enumerate() {
+            arcs.push((f, t, w, e));
+            if kind == GraphKind::Undirected && f != t {
+                arcs.push((t, f, w, e));
+            }
+        }
+        let mut start = vec![0; ids.len() + 1];
+        for a in &arcs {
+            start[a.0 + 1] += 1;
+        }
+        for i in 0..ids.len() {
+            start[i + 1] += start[i];
+        }
+        let mut fill = start.clone();
+        let (mut to, mut weight, mut edge) = (
+            vec![0; arcs.len()],
+            vec![0; arcs.len()],
+            vec![0; arcs.len()],
+        );
+        for (f, t, w, e) in arcs {
+            let k = fill[f];
+            fill[f] += 1;
+            to[k] = t;
+            weight[k] = w;
+            edge[k] = e;
+        }
+        CsrGraph {
+            kind,
+            ids,
+            index,
+            start,
+            to,
+            weight,
+            edge,
+        }
+    }
+
+    /// For graphs whose nodes only exist as edge ends
+    fn from_edges<'a, E: Weighted + 'a>(
+        edges: impl Iterator<Item = (&'a E, &'a ID, &'a ID)>,
+    ) -> Self
+    where
+        ID: 'a,
+    {
+        let mut index = HashMap::new();
+        let mut ids = Vec::new();
+        let mut dense = |id: &ID| {
+            *index.entry(id.clone()).or_insert_with(|| {
+                ids.push(id.clone());
+                ids.len() - 1
+            })
+        };
+        let arcs: Vec<_> = edges
+            .map(|(e, f, t)| (dense(f), dense(t), e.weight()))
+            .collect();
+        Self::build(GraphKind::Directed, ids, index, &arcs)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge.iter().max().map_or(0, |m| m + 1)
+    }
+
+    /// The dense index of id, 0 to node_count
+    pub fn index_of(&self, id: &ID) -> Option<usize> {
+        self.index.get(id).cloned()
+    }
+
+    pub fn id_at(&self, i: usize) -> Option<&ID> {
+        self.ids.get(i)
+    }
+
+    /// (edge, weight, far end index) for each arc leaving the node at index i
+    pub fn arcs(&self, i: usize) -> impl Iterator<Item = (usize, i32, usize)> + '_ {
+        let r = self.start[i]..self.start[i + 1];
+        r.map(move |k| (self.edge[k], self.weight[k], self.to[k]))
+    }
+}
+
+impl<ID: Clone + Hash + Eq> CsrGraph<ID> {
+    /// Arc positions leaving id, empty if it is not here
+    fn arc_range(&self, id: &ID) -> std::ops::Range<usize> {
+        match self.index.get(id) {
+            Some(&i) => self.start[i]..self.start[i + 1],
+            None => 0..0,
+        }
+    }
+}
+
+impl<ID: Clone + Hash + Eq> Walk for CsrGraph<ID> {
+    type Id = ID;
+
+    fn kind(&self) -> GraphKind {
+        self.kind
+    }
+
+    fn node_ids<'a>(&'a self) -> impl Iterator<Item = &'a ID> + 'a {
+        self.ids.iter()
+    }
+
+    fn key<'a>(&'a self, id: &ID) -> Option<&'a ID> {
+        self.index.get(id).map(|i| &self.ids[*i])
+    }
+
+    fn neighbors<'a>(&'a self, id: &ID) -> impl Iterator<Item = &'a ID> + 'a {
+        self.arc_range(id).map(move |k| &self.ids[self.to[k]])
+    }
+}
+
+impl<ID: Clone + Hash + Eq> Traverse for CsrGraph<ID> {
+    type EdgeId = usize;
+
+    fn exits<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a usize, i32, &'a ID)> + 'a {
+        self.arc_range(id)
+            .map(move |k| (&self.edge[k], self.weight[k], &self.ids[self.to[k]]))
+    }
+}
+
+/// Keeps the graph's kind, edge ids become numbers
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> From<&Graph<T, E, ID, EID>>
+    for CsrGraph<ID>
+{
+    fn from(g: &Graph<T, E, ID, EID>) -> Self {
+        let ids: Vec<ID> = g.data.keys().cloned().collect();
+        let index: HashMap<ID, usize> = ids
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, id)| (id, i))
+            .collect();
+        let arcs: Vec<_> = g
+            .edges
+            .values()
+            .map(|(e, f, t)| (index[f], index[t], e.weight()))
+            .collect();
+        Self::build(g.kind(), ids, index, &arcs)
+    }
+}
+
+// The options graphs have no kind, their edges go the way they were added
+
+impl<E: Weighted, ID: Clone + Hash + Eq> From<&EdgeListGraph<E, ID>> for CsrGraph<ID> {
+    fn from(g: &EdgeListGraph<E, ID>) -> Self {
+        Self::from_edges(g.edges().iter().map(|(e, f, t)| (e, f, t)))
+    }
+}
+
+/// Nodes are numbered by their place in the graph, every edge weighs 1
+impl<T> From<&RccGraph<T>> for CsrGraph<usize> {
+    fn from(g: &RccGraph<T>) -> Self {
+        let at: HashMap<*const _, usize> = g
+            .nodes()
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (Rc::as_ptr(n), i))
+            .collect();
+        let mut arcs = Vec::new();
+        for (i, n) in g.nodes().iter().enumerate() {
+            for m in n.borrow().edges() {
+                //a node from some other graph has no place here
+                if let Some(&j) = at.get(&Rc::as_ptr(&m)) {
+                    arcs.push((i, j, 1));
+                }
+            }
+        }
+        let ids: Vec<usize> = (0..g.nodes().len()).collect();
+        let index = ids.iter().map(|i| (*i, *i)).collect();
+        Self::build(GraphKind::Directed, ids, index, &arcs)
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq> From<&MapGraph<T, E, ID>> for CsrGraph<ID> {
+    fn from(g: &MapGraph<T, E, ID>) -> Self {
+        let edges = g
+            .edges()
+            .iter()
+            .map(|(e, f, t)| (f.clone(), t.clone(), e.weight()));
+        Self::new(GraphKind::Directed, g.nodes().keys().cloned(), edges)
+            .expect("MapGraph edges join its nodes")
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq> From<&MapPGraph<T, E, ID>> for CsrGraph<ID> {
+    fn from(g: &MapPGraph<T, E, ID>) -> Self {
+        let edges = g.node_ids().flat_map(|n| {
+            g.out_edges(n)
+                .map(move |(_, e, t)| (n.clone(), t.clone(), e.weight()))
+        });
+        Self::new(GraphKind::Directed, g.node_ids().cloned(), edges)
+            .expect("MapPGraph edges join its nodes")
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq> From<&MapRcGraph<T, E, ID>> for CsrGraph<ID> {
+    fn from(g: &MapRcGraph<T, E, ID>) -> Self {
+        let edges = g.node_ids().flat_map(|n| {
+            g.out_edges(n)
+                .map(move |(e, t)| (n.clone(), t.clone(), e.weight()))
+        });
+        Self::new(GraphKind::Directed, g.node_ids().cloned(), edges)
+            .expect("MapRcGraph edges join its nodes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra::tests::roads;
+
+    #[test]
+    fn test_same_as_graph() {
+        let mut g = roads();
+        g.add_edge('k', 'E', 'E', 2).unwrap();
+        let c = CsrGraph::from(&g);
+        assert_eq!((c.node_count(), c.edge_count()), (8, 11));
+        // the self loop is one arc, like out_edges gives it
+        assert_eq!(c.exits(&'E').count(), g.out_edges(&'E').count());
+        let (gt, ct) = (g.dijkstra(&'A').unwrap(), c.dijkstra(&'A').unwrap());
+        assert_eq!(gt.distances(), ct.distances());
+        assert_eq!(ct.path_to(&'D'), Some(vec!['A', 'H', 'D']));
+        let depths = |s: crate::traverse::Step<char>| (*s.node, s.depth);
+        let mut gb: Vec<_> = g.bfs(&'B').map(depths).collect();
+        let mut cb: Vec<_> = c.bfs(&'B').map(depths).collect();
+        gb.sort();
+        cb.sort();
+        assert_eq!(gb, cb);
+        assert_eq!(c.dfs(&'B').count(), 8);
+        assert_eq!(c.reachable(&'Q').len(), 0);
+        assert_eq!(c.dijkstra(&'Q').err(), Some(GraphErr::MissingNode));
+
+        let i = c.index_of(&'C').unwrap();
+        assert_eq!(c.id_at(i), Some(&'C'));
+        assert_eq!(c.arcs(i).count(), 4);
+    }
+
+    #[test]
+    fn test_new() {
+        let c = CsrGraph::new(
+            GraphKind::Directed,
+            0..4,
+            vec![(0, 1, 5), (1, 2, 1), (0, 2, 9)],
+        )
+        .unwrap();
+        let t = c.dijkstra(&0).unwrap();
+        assert_eq!(t.dist(&2), Some(6));
+        assert_eq!(t.edges_to(&2), Some(vec![0, 1]));
+        assert_eq!(t.dist(&3), None);
+        assert_eq!(c.reachable(&2).len(), 1);
+        assert_eq!(
+            CsrGraph::new(GraphKind::Directed, vec![1, 1], vec![]).err(),
+            Some(GraphErr::DuplicateNode)
+        );
+        assert_eq!(
+            CsrGraph::new(GraphKind::Directed, 0..2, vec![(0, 7, 1)]).err(),
+            Some(GraphErr::MissingNode)
+        );
+        let neg = CsrGraph::new(GraphKind::Undirected, 0..2, vec![(0, 1, -1)]).unwrap();
+        assert_eq!(neg.dijkstra(&0).err(), Some(GraphErr::NegativeWeight));
+    }
+
+    #[test]
+    fn test_from_options() {
+        // 0 -> 1 -> 2 and 0 -> 2 in each, 2 the cheapest reached via 1
+        let mut el = EdgeListGraph::new();
+        let mut mg = MapGraph::new();
+        let mut mp = MapPGraph::new();
+        let mut mr = MapRcGraph::new();
+        let mut rc = RccGraph::new();
+        let rn: Vec<_> = (0..3).map(|i| rc.add_node(i)).collect();
+        for i in 0..3 {
+            mg.add_node(i, ()).unwrap();
+            mp.add_node(i, ()).unwrap();
+            mr.add_node(i, ()).unwrap();
+        }
+        for (e, f, t, w) in [(10, 0, 1, 2), (11, 1, 2, 2), (12, 0, 2, 5)] {
+            el.add_edge(f, t, w);
+            mg.add_edge(f, t, w).unwrap();
+            mp.add_edge(e, f, t, w).unwrap();
+            mr.add_edge(f, t, w).unwrap();
+            rc.add_edge(&rn[f as usize], &rn[t as usize]);
+        }
+        assert_eq!(mg.add_edge(0, 9, 1), Err(GraphErr::MissingNode));
+        assert_eq!(mp.add_edge(10, 1, 0, 1), Err(GraphErr::DuplicateEdge));
+        for c in [
+            CsrGraph::from(&el),
+            CsrGraph::from(&mg),
+            CsrGraph::from(&mp),
+            CsrGraph::from(&mr),
+        ] {
+            assert_eq!((c.node_count(), c.edge_count()), (3, 3));
+            assert_eq!(c.dijkstra(&0).unwrap().dist(&2), Some(4));
+            assert_eq!(c.reachable(&1).len(), 2);
+        }
+        let c = CsrGraph::from(&rc);
+        assert_eq!(c.dijkstra(&0).unwrap().dist(&2), Some(1));
+        assert_eq!(*rc.nodes()[2].borrow().data(), 2);
+        assert_eq!(c.reachable(&2).len(), 1);
+    }
+}
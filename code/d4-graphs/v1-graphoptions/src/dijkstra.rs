@@ -0,0 +1,210 @@
+use crate::graph::{Graph, GraphErr, Weighted};
+use crate::route::Route;
+use crate::traverse::Traverse;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Heap entry, ordered so BinaryHeap pops the smallest dist first
+#[derive(Debug)]
+pub(crate) struct Visit<P> {
+    pub dist: i32,
+    pub pos: P,
+}
+
+impl<P> PartialEq for Visit<P> {
+    fn eq(&self, b: &Self) -> bool {
+        self.dist == b.dist
+    }
+}
+
+impl<P> Eq for Visit<P> {}
+
+impl<P> PartialOrd for Visit<P> {
+    fn partial_cmp(&self, b: &Self) -> Option<Ordering> {
+        Some(self.cmp(b))
+    }
+}
+
+impl<P> Ord for Visit<P> {
+    fn cmp(&self, b: &Self) -> Ordering {
+        b.dist.cmp(&self.dist)
+    }
+}
+
+/// Distances from one source to everything it reaches, and how each was reached
+#[derive(Debug)]
+pub struct ShortestPathTree<ID: Hash + Eq, EID = ID> {
+    source: ID,
+    dist: HashMap<ID, i32>,
+    //node -> (edge used to arrive, node it came from)
+    prev: HashMap<ID, (EID, ID)>,
+}
+
+impl<ID: Clone + Hash + Eq, EID: Clone> ShortestPathTree<ID, EID> {
+    pub(crate) fn new(source: ID, dist: HashMap<ID, i32>, prev: HashMap<ID, (EID, ID)>) -> Self {
+        ShortestPathTree { source, dist, prev }
+    }
+
+    pub fn source(&self) -> &ID {
+        &self.source
+    }
+
+    pub fn dist(&self, to: &ID) -> Option<i32> {
+        self.dist.get(to).cloned()
+    }
+
+    pub fn distances(&self) -> &HashMap<ID, i32> {
+        &self.dist
+    }
+
+    /// The edge used to arrive at a node and the node before it
+    pub fn prev(&self, to: &ID) -> Option<(&EID, &ID)> {
+        self.prev.get(to).map(|(e, n)| (e, n))
+    }
+
+    /// Nodes from the source to to, both included
+    pub fn path_to(&self, to: &ID) -> Option<Vec<ID>> {
+        self.dist.get(to)?;
+        let mut res = vec![to.clone()];
+        let mut pos = to;
+        while let Some((_, n)) = self.prev.get(pos) {
+            res.push(n.clone());
+            pos = n;
+        }
+        res.reverse();
+        Some(res)
+    }
+
+    /// Edge ids from the source to to
+    pub fn edges_to(&self, to: &ID) -> Option<Vec<EID>> {
+        self.dist.get(to)?;
+        let mut res = Vec::new();
+        let mut pos = to;
+        while let Some((e, n)) = self.prev.get(pos) {
+            res.push(e.clone());
+            pos = n;
+        }
+        res.reverse();
+        Some(res)
+    }
+
+    /// The path as a Route, as shortest_path gives
+    pub fn route_to(&self, to: &ID) -> Option<Rc<Route<ID>>> {
+        let mut path = self.path_to(to)?.into_iter();
+        let mut res = Route::start_rc(path.next()?);
+        for pos in path {
+            let len = self.dist[&pos];
+            res = Rc::new(Route {
+                pos,
+                len,
+                path: Some(res),
+            });
+        }
+        Some(res)
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// Shortest paths from source to every node it can reach.
+    /// All weights must be 0 or more, see bellman_ford otherwise
+    pub fn dijkstra(&self, source: &ID) -> Result<ShortestPathTree<ID, EID>, GraphErr> {
+        dijkstra(self, source)
+    }
+}
+
+/// Traverse::dijkstra, for any graph that can list its exits
+pub(crate) fn dijkstra<G: Traverse + ?Sized>(
+    g: &G,
+    source: &G::Id,
+) -> Result<ShortestPathTree<G::Id, G::EdgeId>, GraphErr> {
+    if g.key(source).is_none() {
+        return Err(GraphErr::MissingNode);
+    }
+    if g.node_ids().any(|n| g.exits(n).any(|(_, w, _)| w < 0)) {
+        return Err(GraphErr::NegativeWeight);
+    }
+    let mut dist = HashMap::new();
+    let mut prev = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    dist.insert(source.clone(), 0);
+    heap.push(Visit {
+        dist: 0,
+        pos: source.clone(),
+    });
+    while let Some(Visit { dist: d, pos }) = heap.pop() {
+        //stale entry, a shorter way was found after this was pushed
+        if dist.get(&pos).is_some_and(|best| *best < d) {
+            continue;
+        }
+        for (eid, w, npos) in g.exits(&pos) {
+            let nd = d + w;
+            if dist.get(npos).is_some_and(|best| *best <= nd) {
+                continue;
+            }
+            dist.insert(npos.clone(), nd);
+            prev.insert(npos.clone(), (eid.clone(), pos.clone()));
+            heap.push(Visit {
+                dist: nd,
+                pos: npos.clone(),
+            });
+        }
+    }
+    Ok(ShortestPathTree::new(source.clone(), dist, prev))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::graph::{build, Graph, GraphErr, GraphKind};
+
+    /// Also the fixture for other modules' tests
+    pub(crate) fn roads() -> Graph<(), i32, char> {
+        build(
+            GraphKind::Undirected,
+            "ABCDEFGH".chars(),
+            &[
+                ('a', 'H', 'D', 6),
+                ('b', 'D', 'C', 18),
+                ('c', 'C', 'B', 10),
+                ('d', 'H', 'A', 7),
+                ('e', 'A', 'C', 4),
+                ('f', 'H', 'G', 5),
+                ('g', 'G', 'A', 8),
+                ('h', 'A', 'F', 3),
+                ('i', 'F', 'E', 15),
+                ('j', 'C', 'E', 12),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_dijkstra_tree() {
+        let g = roads();
+        let t = g.dijkstra(&'A').unwrap();
+        assert_eq!(t.dist(&'D'), Some(13));
+        assert_eq!(t.path_to(&'D'), Some(vec!['A', 'H', 'D']));
+        assert_eq!(t.edges_to(&'D'), Some(vec!['d', 'a']));
+        assert_eq!(t.prev(&'E'), Some((&'j', &'C')));
+        assert_eq!(t.path_to(&'A'), Some(vec!['A']));
+        assert_eq!(t.distances().len(), 8);
+        assert_eq!(t.route_to(&'E').unwrap().len, 16);
+        // agrees with the old search everywhere
+        for to in "ABCDEFGH".chars() {
+            assert_eq!(t.dist(&to), g.shortest_path('A', to).map(|r| r.len));
+        }
+    }
+
+    #[test]
+    fn test_dijkstra_errors() {
+        let mut g = roads();
+        g.add_node('Z', ()).unwrap();
+        let t = g.dijkstra(&'Z').unwrap();
+        assert_eq!(t.dist(&'A'), None);
+        assert_eq!(t.path_to(&'A'), None);
+        assert_eq!(*t.source(), 'Z');
+        assert_eq!(g.dijkstra(&'Q').err(), Some(GraphErr::MissingNode));
+        g.set_weight(&'c', -1).unwrap();
+        assert_eq!(g.dijkstra(&'A').err(), Some(GraphErr::NegativeWeight));
+    }
+}
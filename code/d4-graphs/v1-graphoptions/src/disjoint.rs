@@ -0,0 +1,92 @@
+/// Union-find over 0..n, union by rank with path compression
+#[derive(Debug, Clone)]
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    sets: usize,
+}
+
+impl DisjointSet {
+    pub fn new(n: usize) -> Self {
+        DisjointSet {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+            sets: n,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// How many separate sets are left
+    pub fn set_count(&self) -> usize {
+        self.sets
+    }
+
+    /// The root standing for x's set
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        //point everything on the way straight at the root
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    /// Joins the two sets, false if they were already one
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return false;
+        }
+        // the shallower tree goes under the deeper
+        if self.rank[ra] < self.rank[rb] {
+            self.parent[ra] = rb;
+        } else {
+            self.parent[rb] = ra;
+            if self.rank[ra] == self.rank[rb] {
+                self.rank[ra] += 1;
+            }
+        }
+        self.sets -= 1;
+        true
+    }
+
+    pub fn same(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_union_find() {
+        let mut d = DisjointSet::new(8);
+        assert_eq!((d.len(), d.set_count()), (8, 8));
+        assert!(d.union(0, 1) && d.union(2, 3) && d.union(1, 3));
+        assert!(!d.union(0, 2));
+        assert!(d.same(0, 3) && !d.same(0, 4));
+        for i in 4..7 {
+            d.union(i, i + 1);
+        }
+        assert_eq!(d.set_count(), 2);
+        d.union(7, 2);
+        assert_eq!(d.set_count(), 1);
+        let r = d.find(5);
+        assert!((0..8).all(|i| d.find(i) == r));
+        assert!(DisjointSet::new(0).is_empty());
+    }
+}
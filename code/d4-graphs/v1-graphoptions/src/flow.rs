@@ -0,0 +1,570 @@
+use crate::graph::{CycleErr, Graph, GraphErr, GraphKind, Weighted};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// How much can go along an edge, as Weighted says how far it is
+pub trait Capacity {
+    fn capacity(&self) -> i32;
+}
+
+impl Capacity for i32 {
+    fn capacity(&self) -> i32 {
+        *self
+    }
+}
+
+impl Capacity for () {
+    fn capacity(&self) -> i32 {
+        1
+    }
+}
+
+/// An edge with both a capacity and a cost per unit sent, for min_cost_max_flow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pipe {
+    pub cap: i32,
+    pub cost: i32,
+}
+
+impl Capacity for Pipe {
+    fn capacity(&self) -> i32 {
+        self.cap
+    }
+}
+
+impl Weighted for Pipe {
+    fn weight(&self) -> i32 {
+        self.cost
+    }
+}
+
+/// A maximum flow and the minimum cut that limits it
+#[derive(Debug)]
+pub struct Flow<ID: Hash + Eq, EID: Hash + Eq = ID> {
+    pub value: i32,
+    /// Flow along each edge, below 0 if it goes from to to from on an undirected edge
+    pub edge_flow: HashMap<EID, i32>,
+    /// Nodes still reachable from the source with spare capacity
+    pub source_side: HashSet<ID>,
+    /// Edges from source_side to the rest, all full, their capacities sum to value
+    pub cut: Vec<EID>,
+}
+
+struct Arc {
+    to: usize,
+    cap: i32,
+    cost: i32,
+}
+
+//arcs come in pairs, arcs[a ^ 1] is the way back along arcs[a]
+struct Net {
+    adj: Vec<Vec<usize>>,
+    arcs: Vec<Arc>,
+    //first arc of each one way half of an edge: (arc, edge index, +1 or -1)
+    halves: Vec<(usize, usize, i32)>,
+    start_cap: Vec<i32>,
+}
+
+impl Net {
+    fn add(&mut self, from: usize, to: usize, cap: i32, cost: i32) -> usize {
+        let a = self.arcs.len();
+        self.arcs.push(Arc { to, cap, cost });
+        self.arcs.push(Arc {
+            to: from,
+            cap: 0,
+            cost: -cost,
+        });
+        self.adj[from].push(a);
+        self.adj[to].push(a + 1);
+        self.start_cap.extend(&[cap, 0]);
+        a
+    }
+
+    /// Nodes reachable from s through arcs with room left
+    fn reach(&self, s: usize) -> Vec<bool> {
+        let mut seen = vec![false; self.adj.len()];
+        seen[s] = true;
+        let mut q = VecDeque::from(vec![s]);
+        while let Some(n) = q.pop_front() {
+            for a in &self.adj[n] {
+                let to = self.arcs[*a].to;
+                if self.arcs[*a].cap > 0 && !seen[to] {
+                    seen[to] = true;
+                    q.push_back(to);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Sends the most it can along a path given by the arc used to reach each node
+    fn augment(&mut self, s: usize, t: usize, via: &[Option<usize>]) -> i32 {
+        let mut push = i32::MAX;
+        let mut n = t;
+        while n != s {
+            let a = via[n].expect("path reaches back to s");
+            push = push.min(self.arcs[a].cap);
+            n = self.arcs[a ^ 1].to;
+        }
+        let mut n = t;
+        while n != s {
+            let a = via[n].expect("path reaches back to s");
+            self.arcs[a].cap -= push;
+            self.arcs[a ^ 1].cap += push;
+            n = self.arcs[a ^ 1].to;
+        }
+        push
+    }
+
+    fn edmonds_karp(&mut self, s: usize, t: usize) -> i32 {
+        let mut total = 0;
+        loop {
+            // shortest path by edge count
+            let mut via = vec![None; self.adj.len()];
+            let mut q = VecDeque::from(vec![s]);
+            while let Some(n) = q.pop_front() {
+                for a in &self.adj[n] {
+                    let to = self.arcs[*a].to;
+                    if self.arcs[*a].cap > 0 && to != s && via[to].is_none() {
+                        via[to] = Some(*a);
+                        q.push_back(to);
+                    }
+                }
+            }
+            if via[t].is_none() {
+                return total;
+            }
+            total += self.augment(s, t, &via);
+        }
+    }
+
+    fn dinic_push(
+        &mut self,
+        n: usize,
+        t: usize,
+        limit: i32,
+        level: &[usize],
+        next: &mut [usize],
+    ) -> i32 {
+        if n == t {
+            return limit;
+        }
+        while next[n] < self.adj[n].len() {
+            let a = self.adj[n][next[n]];
+            let to = self.arcs[a].to;
+            if self.arcs[a].cap > 0 && level[to] == level[n] + 1 {
+                let pushed = self.dinic_push(to, t, limit.min(self.arcs[a].cap), level, next);
+                if pushed > 0 {
+                    self.arcs[a].cap -= pushed;
+                    self.arcs[a ^ 1].cap += pushed;
+                    return pushed;
+                }
+            }
+            //this arc is no use again until the levels change
+            next[n] += 1;
+        }
+        0
+    }
+
+    fn dinic(&mut self, s: usize, t: usize) -> i32 {
+        let n = self.adj.len();
+        let mut total = 0;
+        loop {
+            let mut level = vec![usize::MAX; n];
+            level[s] = 0;
+            let mut q = VecDeque::from(vec![s]);
+            while let Some(x) = q.pop_front() {
+                for a in &self.adj[x] {
+                    let to = self.arcs[*a].to;
+                    if self.arcs[*a].cap > 0 && level[to] == usize::MAX {
+                        level[to] = level[x] + 1;
+                        q.push_back(to);
+                    }
+                }
+            }
+            if level[t] == usize::MAX {
+                return total;
+            }
+            let mut next = vec![0; n];
+            loop {
+                let pushed = self.dinic_push(s, t, i32::MAX, &level, &mut next);
+                if pushed == 0 {
+                    break;
+                }
+                total += pushed;
+            }
+        }
+    }
+
+    /// Successive cheapest paths, found with SPFA as the way back along an arc costs less than 0
+    /// Err holds the nodes of a negative cost cycle, in order
+    fn min_cost(&mut self, s: usize, t: usize) -> Result<(i32, i32), Vec<usize>> {
+        let n = self.adj.len();
+        let (mut total, mut cost) = (0, 0);
+        loop {
+            let mut dist = vec![i32::MAX; n];
+            let mut via = vec![None; n];
+            let mut queued = vec![false; n];
+            //edges on the best path so far, n or more means it repeats a node
+            let mut steps = vec![0; n];
+            dist[s] = 0;
+            let mut q = VecDeque::from(vec![s]);
+            while let Some(x) = q.pop_front() {
+                queued[x] = false;
+                for a in &self.adj[x] {
+                    let arc = &self.arcs[*a];
+                    if arc.cap > 0 && dist[x] + arc.cost < dist[arc.to] {
+                        dist[arc.to] = dist[x] + arc.cost;
+                        via[arc.to] = Some(*a);
+                        steps[arc.to] = steps[x] + 1;
+                        if steps[arc.to] >= n {
+                            return Err(self.negative_cycle(s));
+                        }
+                        if !queued[arc.to] {
+                            queued[arc.to] = true;
+                            q.push_back(arc.to);
+                        }
+                    }
+                }
+            }
+            if via[t].is_none() {
+                return Ok((total, cost));
+            }
+            let pushed = self.augment(s, t, &via);
+            total += pushed;
+            cost += pushed * dist[t];
+        }
+    }
+
+    /// Plain Bellman-Ford over the residual arcs, only called once SPFA has seen a cycle.
+    /// A node still improving in round n is behind one
+    fn negative_cycle(&self, s: usize) -> Vec<usize> {
+        let n = self.adj.len();
+        let mut dist = vec![i32::MAX; n];
+        let mut via = vec![None; n];
+        dist[s] = 0;
+        let mut last = s;
+        for _ in 0..n {
+            for x in 0..n {
+                if dist[x] == i32::MAX {
+                    continue;
+                }
+                for a in &self.adj[x] {
+                    let arc = &self.arcs[*a];
+                    if arc.cap > 0 && dist[x] + arc.cost < dist[arc.to] {
+                        dist[arc.to] = dist[x] + arc.cost;
+                        via[arc.to] = Some(*a);
+                        last = arc.to;
+                    }
+                }
+            }
+        }
+        self.cycle_at(last, &via)
+    }
+
+    /// Going back n steps from x along via lands on the cycle, then walk it once
+    fn cycle_at(&self, x: usize, via: &[Option<usize>]) -> Vec<usize> {
+        let back = |x: usize| via[x].map_or(x, |a| self.arcs[a ^ 1].to);
+        let mut x = x;
+        for _ in 0..self.adj.len() {
+            x = back(x);
+        }
+        let mut res = vec![x];
+        let mut y = back(x);
+        while y != x {
+            res.push(y);
+            y = back(y);
+        }
+        res.push(x);
+        res.reverse();
+        res
+    }
+}
+
+impl<T, E: Capacity, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// The residual network, and the ids behind its node and edge numbers
+    fn net(&self, cost: impl Fn(&E) -> i32) -> Result<(Net, Vec<&ID>, Vec<&EID>), GraphErr> {
+        let nodes: Vec<&ID> = self.data.keys().collect();
+        let index: HashMap<&ID, usize> = nodes.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+        let mut net = Net {
+            adj: vec![Vec::new(); nodes.len()],
+            arcs: Vec::new(),
+            halves: Vec::new(),
+            start_cap: Vec::new(),
+        };
+        let mut eids = Vec::new();
+        for (eid, (e, from, to)) in &self.edges {
+            if e.capacity() < 0 {
+                return Err(GraphErr::NegativeCapacity);
+            }
+            let (f, t) = (index[from], index[to]);
+            let a = net.add(f, t, e.capacity(), cost(e));
+            net.halves.push((a, eids.len(), 1));
+            //an undirected edge is a pipe each way
+            if self.kind() == GraphKind::Undirected {
+                let b = net.add(t, f, e.capacity(), cost(e));
+                net.halves.push((b, eids.len(), -1));
+            }
+            eids.push(eid);
+        }
+        Ok((net, nodes, eids))
+    }
+
+    fn ends(&self, source: &ID, sink: &ID) -> Result<(usize, usize), GraphErr> {
+        let s = self
+            .data
+            .keys()
+            .position(|k| k == source)
+            .ok_or(GraphErr::MissingNode)?;
+        let t = self
+            .data
+            .keys()
+            .position(|k| k == sink)
+            .ok_or(GraphErr::MissingNode)?;
+        if s == t {
+            return Err(GraphErr::new("source and sink are the same node"));
+        }
+        Ok((s, t))
+    }
+
+    fn flow_result(
+        &self,
+        net: &Net,
+        nodes: &[&ID],
+        eids: &[&EID],
+        s: usize,
+        value: i32,
+    ) -> Flow<ID, EID> {
+        let mut edge_flow: HashMap<EID, i32> = eids.iter().map(|e| ((*e).clone(), 0)).collect();
+        for (a, ei, sign) in &net.halves {
+            *edge_flow.get_mut(eids[*ei]).unwrap() += sign * (net.start_cap[*a] - net.arcs[*a].cap);
+        }
+        let side = net.reach(s);
+        let source_side = (0..nodes.len())
+            .filter(|i| side[*i])
+            .map(|i| nodes[i].clone())
+            .collect();
+        let mut cut = Vec::new();
+        for (a, ei, _) in &net.halves {
+            let from = net.arcs[a ^ 1].to;
+            if side[from] && !side[net.arcs[*a].to] && net.start_cap[*a] > 0 {
+                cut.push(eids[*ei].clone());
+            }
+        }
+        Flow {
+            value,
+            edge_flow,
+            source_side,
+            cut,
+        }
+    }
+
+    /// Most that can get from source to sink at once (Dinic's algorithm, O(V²E))
+    pub fn max_flow(&self, source: &ID, sink: &ID) -> Result<Flow<ID, EID>, GraphErr> {
+        let (s, t) = self.ends(source, sink)?;
+        let (mut net, nodes, eids) = self.net(|_| 0)?;
+        let value = net.dinic(s, t);
+        Ok(self.flow_result(&net, &nodes, &eids, s, value))
+    }
+
+    /// The same as max_flow by shortest augmenting paths, O(VE²), kept to check it by
+    pub fn edmonds_karp(&self, source: &ID, sink: &ID) -> Result<Flow<ID, EID>, GraphErr> {
+        let (s, t) = self.ends(source, sink)?;
+        let (mut net, nodes, eids) = self.net(|_| 0)?;
+        let value = net.edmonds_karp(s, t);
+        Ok(self.flow_result(&net, &nodes, &eids, s, value))
+    }
+}
+
+impl<T, E: Capacity + Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq>
+    Graph<T, E, ID, EID>
+{
+    /// A maximum flow paying as little as it can in weight per unit, and that total cost.
+    /// Costs may be below 0 on directed edges, a negative cycle is an error.
+    /// An undirected edge below 0 is one already, there and back
+    pub fn min_cost_max_flow(
+        &self,
+        source: &ID,
+        sink: &ID,
+    ) -> Result<(Flow<ID, EID>, i32), CycleErr<ID>> {
+        let (s, t) = self.ends(source, sink)?;
+        if self.kind() == GraphKind::Undirected
+            && self.edges.values().any(|(e, _, _)| e.weight() < 0)
+        {
+            return Err(GraphErr::NegativeWeight.into());
+        }
+        let (mut net, nodes, eids) = self.net(|e| e.weight())?;
+        let (value, cost) = net.min_cost(s, t).map_err(|c| {
+            CycleErr::NegativeCycle(c.into_iter().map(|i| nodes[i].clone()).collect())
+        })?;
+        Ok((self.flow_result(&net, &nodes, &eids, s, value), cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn network(kind: GraphKind) -> Graph<(), i32, usize> {
+        // s=0 t=5
+        let mut g = Graph::new(kind);
+        for x in 0..6 {
+            g.add_node(x, ()).unwrap();
+        }
+        for (i, (a, b, c)) in [
+            (0, 1, 16),
+            (0, 2, 13),
+            (2, 1, 4),
+            (1, 3, 12),
+            (3, 2, 9),
+            (2, 4, 14),
+            (4, 3, 7),
+            (3, 5, 20),
+            (4, 5, 4),
+        ]
+        .iter()
+        .enumerate()
+        {
+            g.add_edge(i, *a, *b, *c).unwrap();
+        }
+        g
+    }
+
+    fn check(g: &Graph<(), i32, usize>, f: &Flow<usize>, s: usize, t: usize) {
+        // conservation everywhere but s and t, and within capacity
+        let mut net = [0; 6];
+        for (e, fl) in &f.edge_flow {
+            let (cap, a, b) = g.edge(e).unwrap();
+            assert!(fl.abs() <= *cap);
+            net[*a] -= fl;
+            net[*b] += fl;
+        }
+        assert_eq!((net[s], net[t]), (-f.value, f.value));
+        assert!((0..6).filter(|x| *x != s && *x != t).all(|x| net[x] == 0));
+        let cut: i32 = f.cut.iter().map(|e| *g.edge(e).unwrap().0).sum();
+        assert_eq!(cut, f.value);
+        assert!(f.source_side.contains(&s) && !f.source_side.contains(&t));
+    }
+
+    #[test]
+    fn test_max_flow() {
+        let g = network(GraphKind::Directed);
+        let (d, ek) = (g.max_flow(&0, &5).unwrap(), g.edmonds_karp(&0, &5).unwrap());
+        assert_eq!((d.value, ek.value), (23, 23));
+        check(&g, &d, 0, 5);
+        check(&g, &ek, 0, 5);
+        let mut cut = d.cut.clone();
+        cut.sort();
+        assert_eq!(cut, vec![3, 6, 8]);
+
+        let back = g.max_flow(&5, &0).unwrap();
+        assert_eq!(back.value, 0);
+        assert_eq!(back.source_side.len(), 1);
+        assert_eq!(g.max_flow(&0, &9).err(), Some(GraphErr::MissingNode));
+        assert!(g.max_flow(&0, &0).is_err());
+    }
+
+    #[test]
+    fn test_undirected_flow() {
+        let g = network(GraphKind::Undirected);
+        let (d, ek) = (g.max_flow(&0, &5).unwrap(), g.edmonds_karp(&0, &5).unwrap());
+        assert_eq!(d.value, ek.value);
+        // 24 is all that can leave t's edges, 29 could leave s
+        assert_eq!(d.value, 24);
+        check(&g, &d, 0, 5);
+        check(&g, &g.max_flow(&5, &0).unwrap(), 5, 0);
+    }
+
+    #[test]
+    fn test_min_cost() {
+        // two workers 1 2, two jobs 3 4; worker 1 is cheap on job 4 only
+        let mut g = Graph::new(GraphKind::Directed);
+        for x in 0..6 {
+            g.add_node(x, ()).unwrap();
+        }
+        let p = |cap, cost| Pipe { cap, cost };
+        for (i, (a, b, e)) in [
+            (0, 1, p(1, 0)),
+            (0, 2, p(1, 0)),
+            (1, 3, p(1, 9)),
+            (1, 4, p(1, 2)),
+            (2, 3, p(1, 3)),
+            (2, 4, p(1, 4)),
+            (3, 5, p(1, 0)),
+            (4, 5, p(1, 0)),
+        ]
+        .iter()
+        .enumerate()
+        {
+            g.add_edge(i, *a, *b, *e).unwrap();
+        }
+        let (f, cost) = g.min_cost_max_flow(&0, &5).unwrap();
+        assert_eq!((f.value, cost), (2, 5));
+        assert_eq!((f.edge_flow[&3], f.edge_flow[&4]), (1, 1));
+        assert_eq!(f.edge_flow[&2], 0);
+        assert_eq!(g.max_flow(&0, &5).unwrap().value, 2);
+
+        // 3 -> 4 -> 3 pays back more than it costs, so cheapest has no bottom
+        g.add_edge(8, 3, 4, p(1, 1)).unwrap();
+        g.add_edge(9, 4, 3, p(1, -3)).unwrap();
+        match g.min_cost_max_flow(&0, &5) {
+            Err(CycleErr::NegativeCycle(c)) => {
+                assert_eq!(c.first(), c.last());
+                let mut inner = c[1..].to_vec();
+                inner.sort();
+                assert_eq!(inner, vec![3, 4]);
+            }
+            r => panic!(
+                "expected a negative cycle, got {:?}",
+                r.map(|(f, c)| (f.value, c))
+            ),
+        }
+
+        let mut u = Graph::new(GraphKind::Undirected);
+        for x in 0..2 {
+            u.add_node(x, ()).unwrap();
+        }
+        u.add_edge(0, 0, 1, p(1, -1)).unwrap();
+        assert_eq!(
+            u.min_cost_max_flow(&0, &1).err(),
+            Some(CycleErr::Graph(GraphErr::NegativeWeight))
+        );
+    }
+
+    #[test]
+    fn test_min_cost_negative_dag() {
+        // parallel negative pipes improve node 1 several times in one pass, but there is no cycle
+        let p = |cap, cost| Pipe { cap, cost };
+        for _ in 0..20 {
+            let mut g = Graph::new(GraphKind::Directed);
+            for x in 0..3 {
+                g.add_node(x, ()).unwrap();
+            }
+            g.add_edge(0, 0, 1, p(1, 1)).unwrap();
+            g.add_edge(1, 0, 1, p(1, -1)).unwrap();
+            g.add_edge(2, 0, 1, p(1, -2)).unwrap();
+            g.add_edge(3, 1, 2, p(2, 0)).unwrap();
+            let (f, cost) = g.min_cost_max_flow(&0, &2).unwrap();
+            assert_eq!((f.value, cost), (2, -3));
+        }
+
+        // edges only run from lower to higher nodes, so no cost can loop
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..500 {
+            let mut g = Graph::new(GraphKind::Directed);
+            for x in 0..6 {
+                g.add_node(x, ()).unwrap();
+            }
+            for i in 0..12 {
+                let a = rng.gen_range(0, 5);
+                let b = rng.gen_range(a + 1, 6);
+                g.add_edge(i, a, b, p(rng.gen_range(1, 4), rng.gen_range(-5, 6)))
+                    .unwrap();
+            }
+            let (f, _) = g.min_cost_max_flow(&0, &5).unwrap();
+            assert_eq!(f.value, g.max_flow(&0, &5).unwrap().value);
+        }
+    }
+}
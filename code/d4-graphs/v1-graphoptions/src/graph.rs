@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+#[derive(Debug, PartialEq)]
+pub enum GraphErr {
+    /// add_node with an id already in use
+    DuplicateNode,
+    /// add_edge with an id already in use
+    DuplicateEdge,
+    /// The node is not in the graph, or an edge end is not
+    MissingNode,
+    MissingEdge,
+    /// An edge weight below 0 where the algorithm cannot handle one
+    NegativeWeight,
+    /// An edge capacity below 0
+    NegativeCapacity,
+    /// Text that could not be read as a graph, saying where and why
+    Parse(String),
+    Other(String),
+}
+
+impl GraphErr {
+    pub fn new(s: &str) -> Self {
+        GraphErr::Other(s.to_string())
+    }
+}
+
+impl fmt::Display for GraphErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphErr::DuplicateNode => write!(f, "GraphErr: node id already in graph"),
+            GraphErr::DuplicateEdge => write!(f, "GraphErr: edge id already in graph"),
+            GraphErr::MissingNode => write!(f, "GraphErr: node not in graph"),
+            GraphErr::MissingEdge => write!(f, "GraphErr: edge not in graph"),
+            GraphErr::NegativeWeight => write!(f, "GraphErr: negative edge weight"),
+            GraphErr::NegativeCapacity => write!(f, "GraphErr: negative edge capacity"),
+            GraphErr::Parse(s) => write!(f, "GraphErr: could not parse, {}", s),
+            GraphErr::Other(s) => write!(f, "GraphErr: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for GraphErr {}
+
+/// Errors from algorithms that fail on a cycle, naming the nodes on it in order
+#[derive(Debug, PartialEq)]
+pub enum CycleErr<ID> {
+    Graph(GraphErr),
+    /// A cycle whose weights sum below 0, so no distance through it is shortest
+    NegativeCycle(Vec<ID>),
+    /// Any cycle, where an order needs there to be none
+    Cycle(Vec<ID>),
+}
+
+impl<ID> From<GraphErr> for CycleErr<ID> {
+    fn from(e: GraphErr) -> Self {
+        CycleErr::Graph(e)
+    }
+}
+
+impl<ID: fmt::Debug> fmt::Display for CycleErr<ID> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CycleErr::Graph(e) => write!(f, "{}", e),
+            CycleErr::NegativeCycle(c) => write!(f, "Negative cycle {:?}", c),
+            CycleErr::Cycle(c) => write!(f, "Cycle {:?}", c),
+        }
+    }
+}
+
+impl<ID: fmt::Debug> std::error::Error for CycleErr<ID> {}
+
+pub trait Weighted {
+    fn weight(&self) -> i32;
+}
+
+impl Weighted for i32 {
+    fn weight(&self) -> i32 {
+        *self
+    }
+}
+
+impl Weighted for () {
+    fn weight(&self) -> i32 {
+        1
+    }
+}
+
+/// Whether an edge from a to b can also be walked from b to a
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphKind {
+    Directed,
+    Undirected,
+}
+
+//MapPointer
+/// Edge ids are their own type, EID, which is the node id type unless given
+#[derive(Debug)]
+pub struct Graph<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq = ID> {
+    kind: GraphKind,
+    //each node lists the edges touching it, whichever end it is
+    pub(crate) data: HashMap<ID, (T, Vec<EID>)>,
+    pub(crate) edges: HashMap<EID, (E, ID, ID)>,
+}
+
+impl<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    pub fn new(kind: GraphKind) -> Self {
+        Graph {
+            kind,
+            data: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    pub fn kind(&self) -> GraphKind {
+        self.kind
+    }
+
+    pub fn add_node(&mut self, id: ID, dt: T) -> Result<(), GraphErr> {
+        if self.data.contains_key(&id) {
+            return Err(GraphErr::DuplicateNode);
+        }
+        self.data.insert(id, (dt, Vec::new()));
+        Ok(())
+    }
+
+    pub fn add_edge(&mut self, ed_id: EID, from: ID, to: ID, ed: E) -> Result<(), GraphErr> {
+        if self.edges.contains_key(&ed_id) {
+            return Err(GraphErr::DuplicateEdge);
+        }
+        if !self.data.contains_key(&from) {
+            //check before setting.
+            return Err(GraphErr::MissingNode);
+        }
+        if let Some(ref mut dt) = self.data.get_mut(&to) {
+            self.edges
+                .insert(ed_id.clone(), (ed, from.clone(), to.clone()));
+            dt.1.push(ed_id.clone());
+        } else {
+            return Err(GraphErr::MissingNode);
+        }
+        if from != to {
+            self.data.get_mut(&from).unwrap().1.push(ed_id);
+        }
+        Ok(())
+    }
+
+    /// Returns the edge and its (from, to) ends
+    pub fn remove_edge(&mut self, ed_id: &EID) -> Result<(E, ID, ID), GraphErr> {
+        let (e, from, to) = self.edges.remove(ed_id).ok_or(GraphErr::MissingEdge)?;
+        for end in &[&from, &to] {
+            if let Some(dt) = self.data.get_mut(end) {
+                dt.1.retain(|x| x != ed_id);
+            }
+        }
+        Ok((e, from, to))
+    }
+
+    /// Removes every edge touching the node as well
+    pub fn remove_node(&mut self, id: &ID) -> Result<T, GraphErr> {
+        let (dt, eids) = self.data.remove(id).ok_or(GraphErr::MissingNode)?;
+        for eid in eids {
+            //self loops are already gone from the list with the node
+            if let Some((_, from, to)) = self.edges.remove(&eid) {
+                let other = if from == *id { to } else { from };
+                if let Some(odt) = self.data.get_mut(&other) {
+                    odt.1.retain(|x| *x != eid);
+                }
+            }
+        }
+        Ok(dt)
+    }
+
+    pub fn has_node(&self, id: &ID) -> bool {
+        self.data.contains_key(id)
+    }
+
+    pub fn has_edge(&self, ed_id: &EID) -> bool {
+        self.edges.contains_key(ed_id)
+    }
+
+    pub fn node(&self, id: &ID) -> Option<&T> {
+        self.data.get(id).map(|dt| &dt.0)
+    }
+
+    pub fn node_mut(&mut self, id: &ID) -> Option<&mut T> {
+        self.data.get_mut(id).map(|dt| &mut dt.0)
+    }
+
+    /// The edge with its (from, to) ends
+    pub fn edge(&self, ed_id: &EID) -> Option<(&E, &ID, &ID)> {
+        self.edges.get(ed_id).map(|(e, f, t)| (e, f, t))
+    }
+
+    pub fn edge_mut(&mut self, ed_id: &EID) -> Option<&mut E> {
+        self.edges.get_mut(ed_id).map(|ed| &mut ed.0)
+    }
+
+    /// Replaces the edge's weight (or whatever E holds), returning the old one
+    pub fn set_weight(&mut self, ed_id: &EID, ed: E) -> Result<E, GraphErr> {
+        let old = self.edge_mut(ed_id).ok_or(GraphErr::MissingEdge)?;
+        Ok(std::mem::replace(old, ed))
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// (edge id, edge, far end) for each edge touching id that can be walked
+    /// away from it (leaving) or into it
+    fn incident<'a>(
+        &'a self,
+        id: &ID,
+        leaving: bool,
+    ) -> impl Iterator<Item = (&'a EID, &'a E, &'a ID)> + 'a {
+        let id = id.clone();
+        let kind = self.kind;
+        self.data
+            .get(&id)
+            .into_iter()
+            .flat_map(|dt| dt.1.iter())
+            .filter_map(move |eid| {
+                let (e, from, to) = self.edges.get(eid)?;
+                let (near, far) = if leaving { (from, to) } else { (to, from) };
+                if *near == id {
+                    return Some((eid, e, far));
+                }
+                if kind == GraphKind::Undirected && *far == id {
+                    return Some((eid, e, near));
+                }
+                None
+            })
+    }
+
+    /// Edges that can be walked away from id, with where they lead
+    pub fn out_edges<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a EID, &'a E, &'a ID)> + 'a {
+        self.incident(id, true)
+    }
+
+    /// Edges that can be walked into id, with where they come from
+    pub fn in_edges<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a EID, &'a E, &'a ID)> + 'a {
+        self.incident(id, false)
+    }
+
+    /// Nodes reachable from id in one step
+    pub fn neighbors<'a>(&'a self, id: &ID) -> impl Iterator<Item = &'a ID> + 'a {
+        self.out_edges(id).map(|(_, _, n)| n)
+    }
+}
+
+/// A test graph from its node ids and (edge id, from, to, edge) rows
+#[cfg(test)]
+pub(crate) fn build<E: Clone, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq>(
+    kind: GraphKind,
+    nodes: impl IntoIterator<Item = ID>,
+    edges: &[(EID, ID, ID, E)],
+) -> Graph<(), E, ID, EID> {
+    let mut g = Graph::new(kind);
+    for n in nodes {
+        g.add_node(n, ()).unwrap();
+    }
+    for (eid, from, to, e) in edges {
+        g.add_edge(eid.clone(), from.clone(), to.clone(), e.clone())
+            .unwrap();
+    }
+    g
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_way(kind: GraphKind) -> Graph<(), i32, char> {
+        let mut g = Graph::new(kind);
+        for x in "ABC".chars() {
+            g.add_node(x, ()).unwrap();
+        }
+        g.add_edge('a', 'A', 'B', 1).unwrap();
+        g.add_edge('b', 'B', 'C', 2).unwrap();
+        g.add_edge('c', 'A', 'A', 3).unwrap();
+        g
+    }
+
+    #[test]
+    fn test_directed_edges() {
+        let g = one_way(GraphKind::Directed);
+        let mut n: Vec<_> = g.neighbors(&'A').cloned().collect();
+        n.sort();
+        assert_eq!(n, vec!['A', 'B']);
+        assert_eq!(g.neighbors(&'C').count(), 0);
+        let ins: Vec<_> = g.in_edges(&'C').map(|(e, w, f)| (*e, *w, *f)).collect();
+        assert_eq!(ins, vec![('b', 2, 'B')]);
+        assert_eq!(g.out_edges(&'Z').count(), 0);
+    }
+
+    #[test]
+    fn test_undirected_edges() {
+        let g = one_way(GraphKind::Undirected);
+        let mut n: Vec<_> = g.neighbors(&'B').cloned().collect();
+        n.sort();
+        assert_eq!(n, vec!['A', 'C']);
+        assert_eq!(g.in_edges(&'B').count(), 2);
+        assert_eq!(g.neighbors(&'A').filter(|n| **n == 'A').count(), 1);
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut g = one_way(GraphKind::Directed);
+        assert_eq!(g.add_node('A', ()), Err(GraphErr::DuplicateNode));
+        assert_eq!(g.add_edge('a', 'B', 'C', 9), Err(GraphErr::DuplicateEdge));
+        assert_eq!(g.add_edge('x', 'B', 'Z', 9), Err(GraphErr::MissingNode));
+        assert_eq!(g.set_weight(&'b', 7), Ok(2));
+        *g.edge_mut(&'a').unwrap() += 1;
+        assert_eq!(g.edge(&'a'), Some((&2, &'A', &'B')));
+        assert_eq!(g.edge(&'b'), Some((&7, &'B', &'C')));
+
+        assert_eq!(g.remove_edge(&'a'), Ok((2, 'A', 'B')));
+        assert_eq!(g.remove_edge(&'a'), Err(GraphErr::MissingEdge));
+        assert_eq!(g.in_edges(&'B').count(), 0);
+
+        g.add_edge('d', 'C', 'B', 1).unwrap();
+        assert_eq!(g.remove_node(&'B'), Ok(()));
+        assert!(!g.has_node(&'B'));
+        assert!(!g.has_edge(&'b') && !g.has_edge(&'d') && g.has_edge(&'c'));
+        assert_eq!(g.neighbors(&'C').count(), 0);
+        assert_eq!((g.node_count(), g.edge_count()), (2, 1));
+        assert_eq!(g.remove_node(&'A'), Ok(()));
+        assert_eq!(g.edge_count(), 0);
+        assert_eq!(g.node(&'C'), Some(&()));
+        assert_eq!(g.node_mut(&'A'), None);
+    }
+
+    #[test]
+    fn test_edge_id_type() {
+        // nodes by name, edges by number
+        let mut g: Graph<(), i32, char, u32> = Graph::new(GraphKind::Undirected);
+        for x in "ABC".chars() {
+            g.add_node(x, ()).unwrap();
+        }
+        g.add_edge(0, 'A', 'B', 1).unwrap();
+        g.add_edge(1, 'B', 'C', 2).unwrap();
+        g.add_edge(2, 'A', 'C', 5).unwrap();
+        assert_eq!(g.add_edge(1, 'A', 'B', 1), Err(GraphErr::DuplicateEdge));
+        assert_eq!(g.edge(&1), Some((&2, &'B', &'C')));
+        let ids: Vec<u32> = g.out_edges(&'C').map(|(e, _, _)| *e).collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(g.dijkstra(&'A').unwrap().edges_to(&'C'), Some(vec![0, 1]));
+        assert_eq!(g.kruskal_mst().weight, 3);
+        assert_eq!(g.remove_edge(&2), Ok((5, 'A', 'C')));
+        assert_eq!(g.edge_count(), 2);
+    }
+}
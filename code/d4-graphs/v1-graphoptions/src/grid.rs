@@ -0,0 +1,168 @@
+//! Tile maps as graphs. Node ids are (x, y), the same pair as d6-ecs's Pos,
+//! so an entity can path from (p.x, p.y).
+use crate::graph::{Graph, GraphKind};
+
+/// Cost of one orthogonal step, diagonals cost DIAG, about STEP * sqrt 2
+pub const STEP: i32 = 10;
+pub const DIAG: i32 = 14;
+
+pub type GridId = (i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connect {
+    Four,
+    /// Diagonal moves too, but not squeezing between two walls at a corner
+    Eight,
+}
+
+//forward half of the moves, the graph is undirected so these cover the rest
+const ORTH: [(i32, i32); 2] = [(1, 0), (0, 1)];
+const DIAGS: [(i32, i32); 2] = [(1, 1), (-1, 1)];
+
+pub struct GridGraph {
+    connect: Connect,
+    walls: Vec<char>,
+}
+
+impl GridGraph {
+    /// '#' is the only wall until wall adds others
+    pub fn new(connect: Connect) -> Self {
+        GridGraph {
+            connect,
+            walls: vec!['#'],
+        }
+    }
+
+    pub fn wall(mut self, c: char) -> Self {
+        self.walls.push(c);
+        self
+    }
+
+    /// One node per open tile holding its char. Edge ids are (tile index, move)
+    /// as edge ids have to share the node id type
+    pub fn build(&self, map: &str) -> Graph<char, i32, GridId> {
+        let rows: Vec<Vec<char>> = map.lines().map(|l| l.chars().collect()).collect();
+        let open = |x: i32, y: i32| -> bool {
+            if x < 0 || y < 0 {
+                return false;
+            }
+            match rows.get(y as usize).and_then(|r| r.get(x as usize)) {
+                Some(c) => !self.walls.contains(c),
+                None => false,
+            }
+        };
+        let mut res = Graph::new(GraphKind::Undirected);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if open(x as i32, y as i32) {
+                    res.add_node((x as i32, y as i32), *c).unwrap();
+                }
+            }
+        }
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0) as i32;
+        let mut moves: Vec<((i32, i32), i32)> = ORTH.iter().map(|m| (*m, STEP)).collect();
+        if self.connect == Connect::Eight {
+            moves.extend(DIAGS.iter().map(|m| (*m, DIAG)));
+        }
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..row.len() as i32 {
+                let y = y as i32;
+                if !open(x, y) {
+                    continue;
+                }
+                for (i, ((dx, dy), w)) in moves.iter().enumerate() {
+                    let (nx, ny) = (x + dx, y + dy);
+                    //diagonals need both tiles beside the corner open
+                    if !open(nx, ny) || (*dx != 0 && *dy != 0 && !(open(nx, y) && open(x, ny))) {
+                        continue;
+                    }
+                    let eid = (y * width + x, i as i32);
+                    res.add_edge(eid, (x, y), (nx, ny), *w).unwrap();
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Exact on a 4-connected grid with nothing in the way
+pub fn manhattan(to: GridId) -> impl Fn(&GridId) -> i32 {
+    move |p| STEP * ((p.0 - to.0).abs() + (p.1 - to.1).abs())
+}
+
+/// Straight line, never more than the real cost on either kind of grid.
+/// DIAG is a little under STEP * sqrt(2), so the line is scaled down to match it
+pub fn euclidean(to: GridId) -> impl Fn(&GridId) -> i32 {
+    move |p| {
+        let (dx, dy) = ((p.0 - to.0) as f64, (p.1 - to.1) as f64);
+        let per_step = DIAG as f64 / std::f64::consts::SQRT_2;
+        (per_step * (dx * dx + dy * dy).sqrt()).floor() as i32
+    }
+}
+
+/// Exact on an 8-connected grid with nothing in the way
+pub fn octile(to: GridId) -> impl Fn(&GridId) -> i32 {
+    move |p| {
+        let (dx, dy) = ((p.0 - to.0).abs(), (p.1 - to.1).abs());
+        STEP * dx.max(dy) + (DIAG - STEP) * dx.min(dy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &str = "\
+S..#....
+.#.#.##.
+.#...#..
+.####.#.
+......#G";
+
+    #[test]
+    fn test_four_connected() {
+        let g = GridGraph::new(Connect::Four).build(MAZE);
+        assert_eq!(g.node(&(0, 0)), Some(&'S'));
+        assert!(!g.has_node(&(3, 0)));
+        assert_eq!(g.neighbors(&(0, 0)).count(), 2);
+        let exact = g.dijkstra(&(0, 0)).unwrap().dist(&(7, 4));
+        let r = g.astar((0, 0), (7, 4), manhattan((7, 4))).unwrap();
+        assert_eq!(Some(r.len), exact);
+        let r = g.astar((0, 0), (7, 4), euclidean((7, 4))).unwrap();
+        assert_eq!(Some(r.len), exact);
+        assert_eq!(exact, Some(STEP * 15));
+    }
+
+    #[test]
+    fn test_eight_connected() {
+        let open = "....\n....\n....";
+        let g = GridGraph::new(Connect::Eight).build(open);
+        let r = g.astar((0, 0), (3, 2), octile((3, 2))).unwrap();
+        assert_eq!(r.len, 2 * DIAG + STEP);
+        assert_eq!(r.len, octile((3, 2))(&(0, 0)));
+
+        // no cutting the corner between the two walls
+        let g = GridGraph::new(Connect::Eight).wall('~').build(".#\n~.");
+        assert_eq!(g.neighbors(&(0, 0)).count(), 0);
+        let g = GridGraph::new(Connect::Eight).build(".#\n..");
+        assert_eq!(
+            g.astar((0, 0), (1, 1), euclidean((1, 1))).unwrap().len,
+            2 * STEP
+        );
+    }
+
+    #[test]
+    fn test_euclidean_admissible() {
+        // octile is the real cost on an open 8-connected grid
+        for x in 0..40 {
+            for y in 0..40 {
+                assert!(euclidean((x, y))(&(0, 0)) <= octile((x, y))(&(0, 0)));
+            }
+        }
+        let open = vec![".".repeat(12); 12].join("\n");
+        let g = GridGraph::new(Connect::Eight).build(&open);
+        let r = g.astar((0, 0), (11, 11), euclidean((11, 11))).unwrap();
+        assert_eq!(r.len, 11 * DIAG);
+        assert_eq!(Some(r.len), g.dijkstra(&(0, 0)).unwrap().dist(&(11, 11)));
+    }
+}
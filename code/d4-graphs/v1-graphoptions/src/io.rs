@@ -0,0 +1,540 @@
+//! Reading and writing graphs as Graphviz DOT, a from,to,weight CSV edge list, or JSON.
+//! DOT and CSV are read into String ids with i32 weights, JSON keeps the graph's own types.
+use crate::graph::{Graph, GraphErr, GraphKind, Weighted};
+use crate::route::Route;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::hash::Hash;
+
+fn quote<D: Display>(d: &D) -> String {
+    format!(
+        "\"{}\"",
+        d.to_string().replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok {
+    Id(String),
+    Edge,
+    Open,
+    Close,
+    OpenAttr,
+    CloseAttr,
+    Eq,
+    Sep,
+}
+
+fn tokens(s: &str) -> Result<Vec<Tok>, GraphErr> {
+    let mut res = Vec::new();
+    let mut it = s.chars().peekable();
+    while let Some(c) = it.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '{' => res.push(Tok::Open),
+            '}' => res.push(Tok::Close),
+            '[' => res.push(Tok::OpenAttr),
+            ']' => res.push(Tok::CloseAttr),
+            '=' => res.push(Tok::Eq),
+            ';' | ',' => res.push(Tok::Sep),
+            '#' => while it.next().is_some_and(|c| c != '\n') {},
+            '/' if it.peek() == Some(&'/') => while it.next().is_some_and(|c| c != '\n') {},
+            '-' if it.peek() == Some(&'>') || it.peek() == Some(&'-') => {
+                it.next();
+                res.push(Tok::Edge);
+            }
+            '"' => {
+                let mut id = String::new();
+                loop {
+                    match it.next() {
+                        Some('\\') => id.extend(it.next()),
+                        Some('"') => break,
+                        Some(c) => id.push(c),
+                        None => return Err(GraphErr::Parse("unclosed quote".to_string())),
+                    }
+                }
+                res.push(Tok::Id(id));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' => {
+                let mut id = c.to_string();
+                while let Some(n) = it.peek() {
+                    if !(n.is_alphanumeric() || *n == '_' || *n == '.') {
+                        break;
+                    }
+                    id.push(*n);
+                    it.next();
+                }
+                res.push(Tok::Id(id));
+            }
+            c => return Err(GraphErr::Parse(format!("unexpected {:?}", c))),
+        }
+    }
+    Ok(res)
+}
+
+/// Reads [a=b, c=d] if it is next
+fn attrs(toks: &[Tok], i: &mut usize) -> Result<Vec<(String, String)>, GraphErr> {
+    let mut res = Vec::new();
+    if toks.get(*i) != Some(&Tok::OpenAttr) {
+        return Ok(res);
+    }
+    *i += 1;
+    loop {
+        match (toks.get(*i), toks.get(*i + 1), toks.get(*i + 2)) {
+            (Some(Tok::CloseAttr), _, _) => {
+                *i += 1;
+                return Ok(res);
+            }
+            (Some(Tok::Sep), _, _) => *i += 1,
+            (Some(Tok::Id(k)), Some(Tok::Eq), Some(Tok::Id(v))) => {
+                res.push((k.clone(), v.clone()));
+                *i += 3;
+            }
+            (Some(Tok::Id(k)), _, _) => {
+                res.push((k.clone(), "true".to_string()));
+                *i += 1;
+            }
+            _ => return Err(GraphErr::Parse("bad attribute list".to_string())),
+        }
+    }
+}
+
+/// weight must be a number, a label is only used if it happens to be one
+fn weight_of(at: &[(String, String)]) -> Result<i32, GraphErr> {
+    let find = |key| at.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    if let Some(v) = find("weight") {
+        return v
+            .parse()
+            .map_err(|_| GraphErr::Parse(format!("weight {:?} is not a number", v)));
+    }
+    Ok(find("label").and_then(|v| v.parse().ok()).unwrap_or(1))
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq + Display, EID: Clone + Hash + Eq + Display>
+    Graph<T, E, ID, EID>
+{
+    fn dot_lines(&self, marked: &HashSet<&EID>) -> (Vec<String>, Vec<String>) {
+        let arrow = match self.kind() {
+            GraphKind::Directed => "->",
+            GraphKind::Undirected => "--",
+        };
+        let mut nodes: Vec<String> = self
+            .data
+            .keys()
+            .map(|k| format!("    {};", quote(k)))
+            .collect();
+        let mut edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(eid, (e, f, t))| {
+                let mark = if marked.contains(eid) {
+                    ", color=red, penwidth=2"
+                } else {
+                    ""
+                };
+                format!(
+                    "    {} {} {} [id={}, label={}{}];",
+                    quote(f),
+                    arrow,
+                    quote(t),
+                    quote(eid),
+                    e.weight(),
+                    mark
+                )
+            })
+            .collect();
+        nodes.sort();
+        edges.sort();
+        (nodes, edges)
+    }
+
+    fn dot_with(&self, marked: &HashSet<&EID>, extra: &[String]) -> String {
+        let head = match self.kind() {
+            GraphKind::Directed => "digraph",
+            GraphKind::Undirected => "graph",
+        };
+        let (nodes, edges) = self.dot_lines(marked);
+        let mut res = format!("{} {{\n", head);
+        for l in nodes.iter().chain(extra).chain(&edges) {
+            res.push_str(l);
+            res.push('\n');
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Graphviz source, the weight of each edge as its label
+    pub fn to_dot(&self) -> String {
+        self.dot_with(&HashSet::new(), &[])
+    }
+
+    /// to_dot with the route's nodes boxed in a red cluster and its edges drawn red
+    pub fn to_dot_route(&self, route: &Route<ID>) -> String {
+        let mut stops = vec![&route.pos];
+        let mut r = route;
+        while let Some(p) = &r.path {
+            stops.push(&p.pos);
+            r = p;
+        }
+        stops.reverse();
+        let mut marked = HashSet::new();
+        for w in stops.windows(2) {
+            // the cheapest edge between each pair is the one a shortest route took
+            let best = self
+                .out_edges(w[0])
+                .filter(|(_, _, t)| *t == w[1])
+                .min_by_key(|(_, e, _)| e.weight());
+            if let Some((eid, _, _)) = best {
+                marked.insert(eid);
+            }
+        }
+        let mut extra = vec!["    subgraph cluster_route {".to_string()];
+        extra.push("        label=\"route\"; color=red;".to_string());
+        let mut seen = HashSet::new();
+        for s in stops {
+            if seen.insert(s) {
+                extra.push(format!("        {} [color=red];", quote(s)));
+            }
+        }
+        extra.push("    }".to_string());
+        self.dot_with(&marked, &extra)
+    }
+
+    /// from,to,weight with a header line
+    pub fn to_csv(&self) -> String {
+        let mut lines: Vec<String> = self
+            .edges
+            .values()
+            .map(|(e, f, t)| format!("{},{},{}", f, t, e.weight()))
+            .collect();
+        lines.sort();
+        let mut res = "from,to,weight\n".to_string();
+        for l in lines {
+            res.push_str(&l);
+            res.push('\n');
+        }
+        res
+    }
+}
+
+impl Graph<(), i32, String> {
+    fn ensure_node(&mut self, id: &str) {
+        if !self.has_node(&id.to_string()) {
+            self.add_node(id.to_string(), ())
+                .expect("checked it was missing");
+        }
+    }
+
+    /// Reads the parts of DOT that make a graph: nodes, edge chains, and weight or
+    /// label on edges as the weight (1 otherwise). Edges take their id attribute if given.
+    /// Subgraphs are flattened, styling is ignored
+    pub fn from_dot(s: &str) -> Result<Self, GraphErr> {
+        let toks = tokens(s)?;
+        let mut i = 0;
+        if toks.first() == Some(&Tok::Id("strict".to_string())) {
+            i += 1;
+        }
+        let kind = match toks.get(i) {
+            Some(Tok::Id(k)) if k == "digraph" => GraphKind::Directed,
+            Some(Tok::Id(k)) if k == "graph" => GraphKind::Undirected,
+            _ => return Err(GraphErr::Parse("expected graph or digraph".to_string())),
+        };
+        i += 1;
+        if let Some(Tok::Id(_)) = toks.get(i) {
+            i += 1;
+        }
+        if toks.get(i) != Some(&Tok::Open) {
+            return Err(GraphErr::Parse("expected {".to_string()));
+        }
+        i += 1;
+        let mut res = Graph::new(kind);
+        //ids given anywhere in the file, so made up ones can keep clear of them
+        let given_ids: HashSet<&String> = toks
+            .windows(3)
+            .filter_map(|w| match w {
+                [Tok::Id(k), Tok::Eq, Tok::Id(v)] if k == "id" => Some(v),
+                _ => None,
+            })
+            .collect();
+        let mut next_id = 0;
+        let mut depth = 1;
+        while depth > 0 {
+            match toks.get(i) {
+                None => return Err(GraphErr::Parse("missing }".to_string())),
+                Some(Tok::Close) => {
+                    depth -= 1;
+                    i += 1;
+                }
+                Some(Tok::Open) => {
+                    depth += 1;
+                    i += 1;
+                }
+                Some(Tok::Sep) => i += 1,
+                Some(Tok::Id(k)) if k == "subgraph" => {
+                    i += 1;
+                    if let Some(Tok::Id(_)) = toks.get(i) {
+                        i += 1;
+                    }
+                }
+                Some(Tok::Id(k))
+                    if (k == "node" || k == "edge" || k == "graph")
+                        && toks.get(i + 1) == Some(&Tok::OpenAttr) =>
+                {
+                    i += 1;
+                    attrs(&toks, &mut i)?;
+                }
+                Some(Tok::Id(_)) if toks.get(i + 1) == Some(&Tok::Eq) => i += 3,
+                Some(Tok::Id(first)) => {
+                    let mut chain = vec![first.clone()];
+                    i += 1;
+                    while toks.get(i) == Some(&Tok::Edge) {
+                        match toks.get(i + 1) {
+                            Some(Tok::Id(n)) => chain.push(n.clone()),
+                            _ => {
+                                return Err(GraphErr::Parse(format!(
+                                    "edge from {:?} to nothing",
+                                    first
+                                )))
+                            }
+                        }
+                        i += 2;
+                    }
+                    let at = attrs(&toks, &mut i)?;
+                    for n in &chain {
+                        res.ensure_node(n);
+                    }
+                    if chain.len() < 2 {
+                        continue;
+                    }
+                    let w = weight_of(&at)?;
+                    let given = at.iter().find(|(k, _)| k == "id").map(|(_, v)| v.clone());
+                    for (j, pair) in chain.windows(2).enumerate() {
+                        let eid = match (&given, j) {
+                            (Some(id), 0) => id.clone(),
+                            (Some(id), j) => format!("{}.{}", id, j),
+                            (None, _) => loop {
+                                let id = format!("e{}", next_id);
+                                next_id += 1;
+                                if !res.has_edge(&id) && !given_ids.contains(&id) {
+                                    break id;
+                                }
+                            },
+                        };
+                        res.add_edge(eid, pair[0].clone(), pair[1].clone(), w)?;
+                    }
+                }
+                Some(t) => return Err(GraphErr::Parse(format!("unexpected {:?}", t))),
+            }
+        }
+        Ok(res)
+    }
+
+    /// One from,to[,weight] edge per line, weight 1 if left off. A header line,
+    /// blank lines and # comments are skipped. Edge ids are e0, e1, ...
+    pub fn from_csv(kind: GraphKind, s: &str) -> Result<Self, GraphErr> {
+        let mut res = Graph::new(kind);
+        for (ln, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || (ln == 0 && line.starts_with("from,")) {
+                continue;
+            }
+            let f: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            let w = match f.len() {
+                2 => 1,
+                3 => f[2].parse().map_err(|_| {
+                    GraphErr::Parse(format!("line {}: bad weight {:?}", ln + 1, f[2]))
+                })?,
+                _ => {
+                    return Err(GraphErr::Parse(format!(
+                        "line {}: expected from,to,weight",
+                        ln + 1
+                    )))
+                }
+            };
+            res.ensure_node(f[0]);
+            res.ensure_node(f[1]);
+            let eid = format!("e{}", res.edge_count());
+            res.add_edge(eid, f[0].to_string(), f[1].to_string(), w)?;
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonNode<T, ID> {
+    id: ID,
+    data: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEdge<E, ID, EID> {
+    id: EID,
+    from: ID,
+    to: ID,
+    data: E,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonGraph<T, E, ID, EID> {
+    kind: GraphKind,
+    nodes: Vec<JsonNode<T, ID>>,
+    edges: Vec<JsonEdge<E, ID, EID>>,
+}
+
+impl<
+        T: Serialize,
+        E: Serialize,
+        ID: Clone + Hash + Eq + Serialize,
+        EID: Clone + Hash + Eq + Serialize,
+    > Graph<T, E, ID, EID>
+{
+    /// {"kind", "nodes": [{"id", "data"}], "edges": [{"id", "from", "to", "data"}]}
+    pub fn to_json(&self) -> Result<String, GraphErr> {
+        let j = JsonGraph {
+            kind: self.kind(),
+            nodes: self
+                .data
+                .iter()
+                .map(|(id, (data, _))| JsonNode { id, data })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|(id, (data, from, to))| JsonEdge { id, from, to, data })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&j).map_err(|e| GraphErr::Parse(e.to_string()))
+    }
+}
+
+impl<
+        T: DeserializeOwned,
+        E: DeserializeOwned,
+        ID: Clone + Hash + Eq + DeserializeOwned,
+        EID: Clone + Hash + Eq + DeserializeOwned,
+    > Graph<T, E, ID, EID>
+{
+    pub fn from_json(s: &str) -> Result<Self, GraphErr> {
+        let j: JsonGraph<T, E, ID, EID> =
+            serde_json::from_str(s).map_err(|e| GraphErr::Parse(e.to_string()))?;
+        let mut res = Graph::new(j.kind);
+        for n in j.nodes {
+            res.add_node(n.id, n.data)?;
+        }
+        for e in j.edges {
+            res.add_edge(e.id, e.from, e.to, e.data)?;
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Graph<(), i32, String> {
+        Graph::from_csv(
+            GraphKind::Undirected,
+            "from,to,weight\nH,D,6\nD,C,18\n# a comment\n\nC,B,10\nH,A,7\nA,C,4\nB,E\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let g = sample();
+        assert_eq!((g.node_count(), g.edge_count()), (6, 6));
+        assert_eq!(
+            g.edge(&"e5".to_string()),
+            Some((&1, &"B".to_string(), &"E".to_string()))
+        );
+        let back = Graph::from_csv(GraphKind::Undirected, &g.to_csv()).unwrap();
+        assert_eq!(back.to_csv(), g.to_csv());
+        assert!(g.to_csv().starts_with("from,to,weight\nA,C,4\n"));
+        assert_eq!(
+            Graph::from_csv(GraphKind::Directed, "a,b,x").err(),
+            Some(GraphErr::Parse("line 1: bad weight \"x\"".to_string()))
+        );
+        assert!(Graph::from_csv(GraphKind::Directed, "a").is_err());
+    }
+
+    #[test]
+    fn test_dot() {
+        let g = sample();
+        let dot = g.to_dot();
+        assert!(dot.starts_with("graph {\n"));
+        assert!(dot.contains("\"H\" -- \"D\" [id=\"e0\", label=6];"));
+        let back = Graph::from_dot(&dot).unwrap();
+        assert_eq!(back.to_dot(), dot);
+
+        let hand = r#"
+            strict digraph roads {
+                rankdir=LR;
+                node [shape=box];
+                a -> b -> c [weight=3];  // a chain
+                "big town" -> a [label="5", id=road];
+                subgraph cluster_x { d; }
+            }"#;
+        let d = Graph::from_dot(hand).unwrap();
+        assert_eq!(
+            (d.kind(), d.node_count(), d.edge_count()),
+            (GraphKind::Directed, 5, 3)
+        );
+        assert_eq!(d.edge(&"road".to_string()).unwrap().0, &5);
+        assert_eq!(
+            d.dijkstra(&"big town".to_string())
+                .unwrap()
+                .dist(&"c".to_string()),
+            Some(11)
+        );
+        assert!(Graph::from_dot("digraph { a -> }").is_err());
+        assert!(Graph::from_dot("digraph { a -> b [weight=far]; }").is_err());
+
+        // labelled nodes and text labels on edges are fine, made up ids miss given ones
+        let named = r#"digraph {
+                a [label="Town A"];
+                c -> d; d -> e [label="B road"];
+                a -> b [id=e1];
+                b -> c [id=e0, weight=4];
+            }"#;
+        let d = Graph::from_dot(named).unwrap();
+        assert_eq!((d.node_count(), d.edge_count()), (5, 4));
+        assert_eq!(d.edge(&"e1".to_string()).unwrap().1, &"a".to_string());
+        assert_eq!(d.edge(&"e0".to_string()).unwrap().0, &4);
+        assert_eq!(d.edge(&"e2".to_string()).unwrap().1, &"c".to_string());
+        assert_eq!(
+            d.edge(&"e3".to_string()).unwrap(),
+            (&1, &"d".to_string(), &"e".to_string())
+        );
+        assert!(Graph::from_dot("digraph { a -> b").is_err());
+    }
+
+    #[test]
+    fn test_route_dot() {
+        let g = sample();
+        let r = g.shortest_path("H".to_string(), "B".to_string()).unwrap();
+        let dot = g.to_dot_route(&r);
+        assert!(dot.contains("subgraph cluster_route {"));
+        assert!(dot.contains("\"A\" [color=red];"));
+        assert!(dot.contains("\"A\" -- \"C\" [id=\"e4\", label=4, color=red, penwidth=2];"));
+        assert!(dot.contains("\"D\" -- \"C\" [id=\"e1\", label=18];"));
+        // still readable, the cluster just adds nodes already there
+        assert_eq!(Graph::from_dot(&dot).unwrap().edge_count(), 6);
+    }
+
+    #[test]
+    fn test_json() {
+        let mut g: Graph<String, i32, char> = Graph::new(GraphKind::Directed);
+        g.add_node('A', "home".to_string()).unwrap();
+        g.add_node('B', "work".to_string()).unwrap();
+        g.add_edge('a', 'A', 'B', 12).unwrap();
+        let s = g.to_json().unwrap();
+        let back: Graph<String, i32, char> = Graph::from_json(&s).unwrap();
+        assert_eq!(back.kind(), GraphKind::Directed);
+        assert_eq!(back.node(&'B'), Some(&"work".to_string()));
+        assert_eq!(back.edge(&'a'), Some((&12, &'A', &'B')));
+        assert!(Graph::<(), i32, char>::from_json("{\"kind\":\"Directed\"}").is_err());
+    }
+}
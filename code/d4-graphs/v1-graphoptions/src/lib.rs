@@ -0,0 +1,29 @@
+pub mod allpairs;
+pub mod astar;
+pub mod bellman;
+pub mod csr;
+pub mod dijkstra;
+pub mod disjoint;
+pub mod flow;
+pub mod graph;
+pub mod grid;
+pub mod io;
+pub mod matching;
+pub mod mst;
+pub mod options;
+pub mod order;
+pub mod route;
+pub mod traverse;
+pub mod tsp;
+
+pub use allpairs::DistMatrix;
+pub use csr::CsrGraph;
+pub use dijkstra::ShortestPathTree;
+pub use disjoint::DisjointSet;
+pub use flow::{Capacity, Flow, Pipe};
+pub use graph::{CycleErr, Graph, GraphErr, GraphKind, Weighted};
+pub use grid::{Connect, GridGraph};
+pub use mst::SpanningTree;
+pub use route::Route;
+pub use traverse::{DfsVisitor, Step, Traverse, Walk};
+pub use tsp::{Anneal, Tour, Tsp};
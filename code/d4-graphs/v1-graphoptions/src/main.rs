@@ -1,240 +1,12 @@
-use rand::prelude::*;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::fmt;
-use std::hash::Hash;
-use std::rc::Rc;
-
-#[derive(Debug)]
-pub struct GraphErr {
-    mess: String,
-}
-
-impl GraphErr {
-    pub fn new(s: &str) -> Self {
-        GraphErr {
-            mess: s.to_string(),
-        }
-    }
-}
-
-pub trait Weighted {
-    fn weight(&self) -> i32;
-}
-
-impl Weighted for i32 {
-    fn weight(&self) -> i32 {
-        *self
-    }
-}
-
-impl Weighted for () {
-    fn weight(&self) -> i32 {
-        return 1;
-    }
-}
-
-//MapPointer
-#[derive(Debug)]
-pub struct Graph<T, E, ID: Clone + Hash + Eq> {
-    data: HashMap<ID, (T, Vec<ID>)>,
-    edges: HashMap<ID, (E, ID, ID)>,
-}
-
-impl<T, E, ID: Clone + Hash + Eq> Graph<T, E, ID> {
-    pub fn new() -> Self {
-        Graph {
-            data: HashMap::new(),
-            edges: HashMap::new(),
-        }
-    }
-
-    pub fn add_node(&mut self, id: ID, dt: T) {
-        self.data.insert(id, (dt, Vec::new()));
-    }
-
-    pub fn add_edge(&mut self, ed_id: ID, from: ID, to: ID, ed: E) -> Result<(), GraphErr> {
-        if !self.data.contains_key(&from) {
-            //check before setting.
-            return Err(GraphErr::new("from not in nodes"));
-        }
-        if let Some(ref mut dt) = self.data.get_mut(&to) {
-            self.edges.insert(ed_id.clone(), (ed, from.clone(), to));
-            dt.1.push(ed_id.clone());
-        } else {
-            return Err(GraphErr::new("to not in nodes"));
-        }
-        self.data.get_mut(&from).unwrap().1.push(ed_id);
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
-pub struct Route<ID> {
-    pos: ID,
-    path: Option<Rc<Route<ID>>>,
-    len: i32,
-}
-
-impl<ID: Eq> Route<ID> {
-    pub fn start_rc(pos: ID) -> Rc<Self> {
-        Rc::new(Route {
-            pos,
-            path: None,
-            len: 0,
-        })
-    }
-
-    pub fn contains(&self, id: &ID) -> bool {
-        if self.pos == *id {
-            return true;
-        }
-        match self.path {
-            Some(ref p) => p.contains(id),
-            None => false,
-        }
-    }
-}
-
-impl<ID: std::fmt::Debug> fmt::Display for Route<ID> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let Some(ref p) = self.path {
-            write!(f, "{}-{}-", p, self.len)?;
-        }
-        write!(f, "{:?}", self.pos)
-    }
-}
-
-impl<T, E: Weighted, ID: Clone + Hash + Eq + fmt::Debug> Graph<T, E, ID> {
-    pub fn shortest_path(&self, from: ID, to: ID) -> Option<Rc<Route<ID>>> {
-        self.shortest_path_r(Route::start_rc(from), to)
-    }
-
-    pub fn shortest_path_r(&self, sofar: Rc<Route<ID>>, to: ID) -> Option<Rc<Route<ID>>> {
-        let mut toset = HashSet::new();
-        toset.insert(to);
-        self.closest(sofar, &toset)
-    }
-
-    //return list of edges
-    pub fn closest(&self, from: Rc<Route<ID>>, to: &HashSet<ID>) -> Option<Rc<Route<ID>>> {
-        let mut visited = HashSet::new();
-        let mut routes = Vec::new();
-        routes.push(from.clone());
-        loop {
-            let c_route = routes.pop()?;
-            //To win must be the top route
-            if to.contains(&c_route.pos) {
-                return Some(c_route);
-            }
-            if visited.contains(&c_route.pos) {
-                //This is here because if we do it at add, that means we won't
-                //add a shorter route if we find one
-                continue;
-            }
-            visited.insert(c_route.pos.clone());
-            let exits = self.data.get(&c_route.pos).unwrap();
-            // insert routes in correct order
-            for eid in &exits.1 {
-                let edge = self.edges.get(eid)?;
-                let npos = if edge.1 == c_route.pos {
-                    edge.2.clone()
-                } else {
-                    edge.1.clone()
-                };
-                let nlen = c_route.len + edge.0.weight();
-                //insert sorted
-                let nroute = Rc::new(Route {
-                    pos: npos,
-                    len: nlen,
-                    path: Some(c_route.clone()),
-                });
-
-                if routes.len() == 0 {
-                    routes.push(nroute);
-                    continue;
-                }
-
-                let mut i_after = routes.len() - 1;
-                loop {
-                    if routes[i_after].len > nlen {
-                        routes.insert(i_after + 1, nroute);
-                        break;
-                    }
-                    if i_after == 0 {
-                        routes.insert(0, nroute);
-                        break;
-                    }
-                    i_after -= 1;
-                }
-            }
-        }
-    }
-
-    pub fn greedy_salesman(&self, start: ID) -> Option<Rc<Route<ID>>> {
-        let mut to_visit: HashSet<ID> = self.data.keys().map(|k| k.clone()).collect();
-        to_visit.remove(&start);
-        let mut route = Route::start_rc(start.clone());
-        while to_visit.len() > 0 {
-            route = self.closest(route, &to_visit)?;
-            to_visit.remove(&route.pos);
-        }
-        route = self.shortest_path_r(route, start)?;
-        Some(route)
-    }
-
-    pub fn complete_path(&self, path: &[ID]) -> Option<Rc<Route<ID>>> {
-        if path.len() < 2 {
-            return None;
-        }
-        let mut route = Route::start_rc(path[0].clone());
-        for pos in &path[1..path.len() - 1] {
-            if !route.contains(pos) {
-                route = self.shortest_path_r(route, pos.clone())?;
-            }
-        }
-        //include final elem without checking
-        route = self.shortest_path_r(route, path[path.len() - 1].clone())?;
-
-        Some(route)
-    }
-
-    pub fn iter_salesman(&self, start: ID) -> Option<Rc<Route<ID>>> {
-        let mut bpath: Vec<ID> = self.data.keys().map(|k| k.clone()).collect();
-        bpath.shuffle(&mut rand::thread_rng());
-        for n in 0..bpath.len() {
-            if bpath[n] == start {
-                bpath.swap(0, n);
-                break;
-            }
-        }
-        bpath.push(start); //start and finish = A
-        let mut broute = self.complete_path(&bpath)?;
-        let mut no_imp = 0;
-        loop {
-            let mut p2 = bpath.clone();
-            let sa = (rand::random::<usize>() % (p2.len() - 2)) + 1;
-            let sb = (rand::random::<usize>() % (p2.len() - 2)) + 1;
-            p2.swap(sa, sb);
-            let r2 = self.complete_path(&p2)?;
-            if r2.len < broute.len {
-                println!("Improves = {}", r2);
-                bpath = p2;
-                broute = r2;
-                no_imp = 0;
-            }
-            no_imp += 1;
-            if no_imp == 50 {
-                return Some(broute);
-            }
-        }
-    }
-}
+use v1_graphoptions::grid::octile;
+use v1_graphoptions::{
+    Anneal, Connect, CsrGraph, Graph, GraphErr, GraphKind, GridGraph, Traverse, Walk,
+};
 
 fn main() -> Result<(), GraphErr> {
-    let mut g = Graph::new();
-    for x in vec!['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H'] {
-        g.add_node(x, ());
+    let mut g = Graph::new(GraphKind::Undirected);
+    for x in ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H'] {
+        g.add_node(x, ())?;
     }
     g.add_edge('a', 'H', 'D', 6)?;
     g.add_edge('b', 'D', 'C', 18)?;
@@ -251,6 +23,102 @@ fn main() -> Result<(), GraphErr> {
 
     println!("A - D = {}", g.shortest_path('A', 'D').unwrap());
 
+    let tree = g.dijkstra(&'A')?;
+    for to in "BCDEFGH".chars() {
+        println!(
+            "A - {} = {:?} via {:?}",
+            to,
+            tree.dist(&to),
+            tree.path_to(&to).unwrap_or_default()
+        );
+    }
+
+    // A toll refund makes one road pay, Dijkstra refuses it but Bellman-Ford copes
+    let mut neg = Graph::new(GraphKind::Directed);
+    for x in "ABCD".chars() {
+        neg.add_node(x, ())?;
+    }
+    neg.add_edge('a', 'A', 'B', 4)?;
+    neg.add_edge('b', 'A', 'C', 2)?;
+    neg.add_edge('c', 'C', 'B', -3)?;
+    neg.add_edge('d', 'B', 'D', 2)?;
+    println!("dijkstra with refund = {:?}", neg.dijkstra(&'A').err());
+    let bf = neg.bellman_ford(&'A').unwrap();
+    println!(
+        "bellman_ford A - D = {:?} via {:?}",
+        bf.dist(&'D'),
+        bf.path_to(&'D')
+    );
+    neg.add_edge('e', 'D', 'C', 0)?;
+    match neg.spfa(&'A') {
+        Err(e) => println!("spfa: {}", e),
+        Ok(t) => println!("spfa: {:?}", t.distances()),
+    }
+
+    let map = "\
+S....#....
+.###.#.##.
+...#...#..
+.#.#####.#
+.#.......G";
+    let grid = GridGraph::new(Connect::Eight).build(map);
+    match grid.astar((0, 0), (9, 4), octile((9, 4))) {
+        Some(r) => println!("grid S - G costs {} via {}", r.len, r),
+        None => println!("grid S - G blocked"),
+    }
+
+    let all = g.all_pairs_shortest_paths().unwrap();
+    println!(
+        "all pairs B - G = {:?} via {:?}",
+        all.dist(&'B', &'G'),
+        all.path(&'B', &'G')
+    );
+
+    for s in g.bfs(&'B') {
+        println!("bfs {:?} depth {} from {:?}", s.node, s.depth, s.parent);
+    }
+    println!("has cycle = {}", g.has_cycle());
+
+    let mst = g.kruskal_mst();
+    println!(
+        "mst {:?} weight {}, prim weight {}",
+        mst.edges,
+        mst.weight,
+        g.prim_mst().weight
+    );
+    println!("mst tour A = {}", g.mst_salesman('A').unwrap());
+
+    let tsp = g.tsp().unwrap();
+    let nn = tsp.nearest_neighbour(&'A').unwrap();
+    println!("tsp nearest {}", nn);
+    println!("tsp 2-opt + or-opt {}", tsp.local_search(&nn));
+    println!("tsp annealed {}", tsp.anneal(&nn, &Anneal::default()));
+    println!("tsp exact {}", tsp.held_karp(&'A').unwrap());
+
+    // Road weights as lane capacities, how many cars an hour from A to E
+    let flow = g.max_flow(&'A', &'E')?;
+    println!("max flow A - E = {}, cut by {:?}", flow.value, flow.cut);
+
+    println!("bipartite = {}", g.is_bipartite());
+    let (pairs, cost) = g.min_cost_assignment(&['A', 'B', 'D'], &['C', 'H', 'E']);
+    println!("assign A B D to C H E = {:?} cost {}", pairs, cost);
+
+    // The same roads from a CSV edge list, and the best route drawn in Graphviz
+    let csv = "from,to,weight\nH,D,6\nD,C,18\nC,B,10\nH,A,7\nA,C,4\nH,G,5\nG,A,8\nA,F,3\nF,E,15\nC,E,12\n";
+    let loaded = Graph::from_csv(GraphKind::Undirected, csv)?;
+    let route = loaded
+        .shortest_path("B".to_string(), "G".to_string())
+        .unwrap();
+    println!("{}", loaded.to_dot_route(&route));
+
+    // Packed flat for reading, the same searches give the same answers
+    let csr = CsrGraph::from(&g);
+    println!(
+        "csr A - D = {:?}, reaches {}",
+        csr.dijkstra(&'A')?.dist(&'D'),
+        csr.reachable(&'A').len()
+    );
+
     println!("greedy A = {}", g.greedy_salesman('A').unwrap());
 
     println!(
@@ -259,5 +127,35 @@ fn main() -> Result<(), GraphErr> {
             .unwrap()
     );
     println!("iter_saleseman = {}", g.iter_salesman('A').unwrap());
+
+    // The same roads made one way, only the listed direction can be driven
+    let mut d = Graph::new(GraphKind::Directed);
+    for x in "ABCD".chars() {
+        d.add_node(x, ())?;
+    }
+    d.add_edge('a', 'A', 'B', 1)?;
+    d.add_edge('b', 'B', 'C', 1)?;
+    d.add_edge('c', 'C', 'D', 1)?;
+    d.add_edge('d', 'D', 'A', 1)?;
+    println!("one way B - A = {}", d.shortest_path('B', 'A').unwrap());
+    println!(
+        "into A from {:?}",
+        d.in_edges(&'A').map(|(_, _, f)| f).collect::<Vec<_>>()
+    );
+
+    println!("one way has cycle = {}", d.has_cycle());
+    match d.topological_sort() {
+        Ok(o) => println!("one way order {:?}", o),
+        Err(e) => println!("one way order: {}", e),
+    }
+    println!("one way components {:?}", d.strongly_connected_components());
+
+    // Closing D leaves no way back round to A
+    d.remove_node(&'D')?;
+    println!(
+        "without D, B - A = {:?}",
+        d.shortest_path('B', 'A').map(|r| r.len)
+    );
+    println!("without D, order {:?}", d.topological_sort());
     Ok(())
 }
@@ -0,0 +1,413 @@
+use crate::graph::{Graph, Weighted};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Cheapest way to give each row its own column (the Hungarian algorithm, O(n²m)).
+/// A row shorter than the longest has no entry for the columns past its end.
+/// Gives the column for each row, None if it could not be given one it has an entry for,
+/// and the total. As many rows as possible are given a column before cost is looked at
+pub fn min_cost_assignment(cost: &[Vec<i32>]) -> (Vec<Option<usize>>, i64) {
+    let cols = cost.iter().map(|r| r.len()).max().unwrap_or(0);
+    let big: Vec<Vec<Option<i64>>> = cost
+        .iter()
+        .map(|r| (0..cols).map(|j| r.get(j).map(|c| *c as i64)).collect())
+        .collect();
+    hungarian(&big, cols)
+}
+
+/// cost is rows by cols, None where a row may not take that column
+fn hungarian(cost: &[Vec<Option<i64>>], cols: usize) -> (Vec<Option<usize>>, i64) {
+    let rows = cost.len();
+    // a missing pair costs more than any spread of real totals, so one fewer is always
+    // better, yet small enough that n of them cannot overflow
+    let spread: i64 = cost.iter().flatten().flatten().map(|c| c.abs()).sum();
+    let no_edge = 2 * spread + 1;
+    // square it up, padding costs nothing, a row given a pad column gets nothing
+    let n = rows.max(cols);
+    let at = |i: usize, j: usize| -> i64 {
+        if i < rows && j < cols {
+            cost[i][j].unwrap_or(no_edge)
+        } else {
+            0
+        }
+    };
+    // potentials u v, p[j] is the row on column j, all 1 based with 0 as a spare
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let cur = at(i0 - 1, j - 1) - u[i0] - v[j];
+                if cur < minv[j] {
+                    minv[j] = cur;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+        //flip the alternating path back to the start
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+    let mut res = vec![None; rows];
+    let mut total = 0;
+    for (j, row) in p.iter().enumerate().skip(1) {
+        let (i, j) = (row - 1, j - 1);
+        if i < rows && j < cols {
+            if let Some(c) = cost[i][j] {
+                res[i] = Some(j);
+                total += c;
+            }
+        }
+    }
+    (res, total)
+}
+
+impl<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// Splits the nodes in two so every edge goes between the sides, ignoring direction.
+    /// None if an odd cycle makes that impossible
+    pub fn two_colouring(&self) -> Option<(Vec<ID>, Vec<ID>)> {
+        let mut colour: HashMap<&ID, bool> = HashMap::new();
+        for root in self.data.keys() {
+            if colour.contains_key(root) {
+                continue;
+            }
+            colour.insert(root, false);
+            let mut q = VecDeque::from(vec![root]);
+            while let Some(n) = q.pop_front() {
+                let c = colour[n];
+                for (_, _, m) in self.out_edges(n).chain(self.in_edges(n)) {
+                    match colour.get(m) {
+                        Some(mc) if *mc == c => return None,
+                        Some(_) => {}
+                        None => {
+                            colour.insert(m, !c);
+                            q.push_back(m);
+                        }
+                    }
+                }
+            }
+        }
+        let (a, b): (Vec<_>, Vec<_>) = colour.into_iter().partition(|(_, c)| !c);
+        Some((
+            a.into_iter().map(|(k, _)| k.clone()).collect(),
+            b.into_iter().map(|(k, _)| k.clone()).collect(),
+        ))
+    }
+
+    pub fn is_bipartite(&self) -> bool {
+        self.two_colouring().is_some()
+    }
+
+    /// As many pairs joined by an edge as can be made with no node used twice
+    /// (Hopcroft-Karp, O(E √V)). Pairs are (first side, second side) of two_colouring.
+    /// None if the graph is not bipartite
+    pub fn max_bipartite_matching(&self) -> Option<Vec<(ID, ID)>> {
+        let (left, right) = self.two_colouring()?;
+        let ri: HashMap<&ID, usize> = right.iter().enumerate().map(|(i, r)| (r, i)).collect();
+        let adj: Vec<Vec<usize>> = left
+            .iter()
+            .map(|l| {
+                self.out_edges(l)
+                    .chain(self.in_edges(l))
+                    .map(|(_, _, r)| ri[r])
+                    .collect()
+            })
+            .collect();
+        let mut hk = HopcroftKarp {
+            adj,
+            pair_l: vec![None; left.len()],
+            pair_r: vec![None; right.len()],
+            dist: vec![0; left.len()],
+        };
+        while hk.bfs() {
+            for l in 0..left.len() {
+                if hk.pair_l[l].is_none() {
+                    hk.dfs(l);
+                }
+            }
+        }
+        Some(
+            hk.pair_l
+                .iter()
+                .enumerate()
+                .filter_map(|(l, r)| Some((left[l].clone(), right[(*r)?].clone())))
+                .collect(),
+        )
+    }
+}
+
+struct HopcroftKarp {
+    adj: Vec<Vec<usize>>,
+    pair_l: Vec<Option<usize>>,
+    pair_r: Vec<Option<usize>>,
+    dist: Vec<usize>,
+}
+
+impl HopcroftKarp {
+    /// Layers left nodes by distance from a free one, true if a free right node is reachable
+    fn bfs(&mut self) -> bool {
+        let mut q = VecDeque::new();
+        for l in 0..self.adj.len() {
+            if self.pair_l[l].is_none() {
+                self.dist[l] = 0;
+                q.push_back(l);
+            } else {
+                self.dist[l] = usize::MAX;
+            }
+        }
+        let mut found = false;
+        while let Some(l) = q.pop_front() {
+            for r in &self.adj[l] {
+                match self.pair_r[*r] {
+                    None => found = true,
+                    Some(l2) if self.dist[l2] == usize::MAX => {
+                        self.dist[l2] = self.dist[l] + 1;
+                        q.push_back(l2);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        found
+    }
+
+    fn dfs(&mut self, l: usize) -> bool {
+        for i in 0..self.adj[l].len() {
+            let r = self.adj[l][i];
+            let ok = match self.pair_r[r] {
+                None => true,
+                Some(l2) => self.dist[l2] == self.dist[l] + 1 && self.dfs(l2),
+            };
+            if ok {
+                self.pair_l[l] = Some(r);
+                self.pair_r[r] = Some(l);
+                return true;
+            }
+        }
+        //dead end, skip it for the rest of this phase
+        self.dist[l] = usize::MAX;
+        false
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// Gives each of left its own one of right as cheaply as possible, by the weight of the
+    /// edge between them. Pairs without an edge are never made. Returns the pairs and total
+    pub fn min_cost_assignment(&self, left: &[ID], right: &[ID]) -> (Vec<(ID, ID)>, i64) {
+        let ri: HashMap<&ID, usize> = right.iter().enumerate().map(|(i, r)| (r, i)).collect();
+        let mut cost: Vec<Vec<Option<i64>>> = vec![vec![None; right.len()]; left.len()];
+        for (i, l) in left.iter().enumerate() {
+            for (_, e, r) in self.out_edges(l).chain(self.in_edges(l)) {
+                if let Some(j) = ri.get(r) {
+                    let w = e.weight() as i64;
+                    cost[i][*j] = Some(cost[i][*j].map_or(w, |c| c.min(w)));
+                }
+            }
+        }
+        let (cols, total) = hungarian(&cost, right.len());
+        let res = cols
+            .iter()
+            .enumerate()
+            .filter_map(|(i, j)| Some((left[i].clone(), right[(*j)?].clone())))
+            .collect();
+        (res, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{build, GraphKind};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const U: GraphKind = GraphKind::Undirected;
+
+    #[test]
+    fn test_bipartite() {
+        let square = build(
+            U,
+            0..4,
+            &[(0, 0, 1, 1), (1, 1, 2, 1), (2, 2, 3, 1), (3, 3, 0, 1)],
+        );
+        let (a, b) = square.two_colouring().unwrap();
+        let (mut a, mut b) = (a, b);
+        a.sort();
+        b.sort();
+        assert!((a == vec![0, 2] && b == vec![1, 3]) || (a == vec![1, 3] && b == vec![0, 2]));
+        let triangle = build(U, 0..3, &[(0, 0, 1, 1), (1, 1, 2, 1), (2, 2, 0, 1)]);
+        assert!(!triangle.is_bipartite());
+        assert!(triangle.max_bipartite_matching().is_none());
+    }
+
+    #[test]
+    fn test_hopcroft_karp() {
+        // workers 0..5 and shifts 10..15, 4 and 5 can only do shift 14
+        let can = [
+            (0, 10),
+            (0, 11),
+            (1, 10),
+            (2, 11),
+            (2, 12),
+            (3, 12),
+            (3, 13),
+            (3, 15),
+            (4, 14),
+            (5, 14),
+        ];
+        let rows: Vec<_> = can
+            .iter()
+            .enumerate()
+            .map(|(i, (a, b))| (i, *a, *b, 1))
+            .collect();
+        let g = build(U, (0..6).chain(10..16), &rows);
+        let m = g.max_bipartite_matching().unwrap();
+        assert_eq!(m.len(), 5);
+        let mut used = std::collections::HashSet::new();
+        for (a, b) in &m {
+            assert!(can.contains(&(*a.min(b), *a.max(b))));
+            assert!(used.insert(*a) && used.insert(*b));
+        }
+    }
+
+    fn brute(cost: &[Vec<i32>]) -> i64 {
+        fn go(cost: &[Vec<i32>], row: usize, used: &mut Vec<bool>) -> i64 {
+            if row == cost.len() {
+                return 0;
+            }
+            let mut best = i64::MAX;
+            for j in 0..used.len() {
+                if !used[j] {
+                    used[j] = true;
+                    best = best.min(cost[row][j] as i64 + go(cost, row + 1, used));
+                    used[j] = false;
+                }
+            }
+            best
+        }
+        go(cost, 0, &mut vec![false; cost[0].len()])
+    }
+
+    #[test]
+    fn test_hungarian() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (rows, cols) in &[(4, 4), (6, 6), (3, 5), (5, 7)] {
+            let cost: Vec<Vec<i32>> = (0..*rows)
+                .map(|_| (0..*cols).map(|_| rng.gen_range(-5, 40)).collect())
+                .collect();
+            let (asg, total) = min_cost_assignment(&cost);
+            assert_eq!(total, brute(&cost));
+            let mut cols_used: Vec<usize> = asg.iter().map(|c| c.unwrap()).collect();
+            let sum: i64 = asg
+                .iter()
+                .enumerate()
+                .map(|(i, c)| cost[i][c.unwrap()] as i64)
+                .sum();
+            assert_eq!(sum, total);
+            cols_used.sort();
+            cols_used.dedup();
+            assert_eq!(cols_used.len(), *rows);
+        }
+        // more rows than columns leaves one out
+        let (asg, total) = min_cost_assignment(&[vec![1], vec![0], vec![5]]);
+        assert_eq!((asg, total), (vec![None, Some(0), None], 0));
+        // short rows have nothing past their end, so no made up pairs
+        let (asg, total) = min_cost_assignment(&[vec![4, 1], vec![], vec![3]]);
+        assert_eq!((asg, total), (vec![Some(1), None, Some(0)], 4));
+        let (asg, total) = min_cost_assignment(&[vec![], vec![], vec![7]]);
+        assert_eq!((asg, total), (vec![None, None, Some(0)], 7));
+    }
+
+    #[test]
+    fn test_graph_assignment() {
+        let g = build(
+            U,
+            0..6,
+            &[
+                (0, 0, 3, 9),
+                (1, 0, 4, 2),
+                (2, 1, 3, 3),
+                (3, 1, 4, 4),
+                (4, 2, 5, 1),
+                (5, 1, 5, 1),
+            ],
+        );
+        let (mut pairs, total) = g.min_cost_assignment(&[0, 1, 2], &[3, 4, 5]);
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 4), (1, 3), (2, 5)]);
+        assert_eq!(total, 6);
+        // 2 has no edge to anything left, so goes without
+        let (pairs, total) = g.min_cost_assignment(&[0, 2], &[3, 4]);
+        assert_eq!((pairs, total), (vec![(0, 4)], 2));
+
+        // many left nodes with no edges at all
+        let empty = build::<i32, _, i32>(U, 0..10, &[]);
+        let left: Vec<i32> = (0..5).collect();
+        let right: Vec<i32> = (5..10).collect();
+        assert_eq!(empty.min_cost_assignment(&left, &right), (vec![], 0));
+        let g = build(
+            U,
+            0..12,
+            &[(0, 0, 6, 5), (1, 3, 6, 2), (2, 3, 7, 1), (3, 5, 11, -4)],
+        );
+        let left: Vec<i32> = (0..6).collect();
+        let right: Vec<i32> = (6..12).collect();
+        let (mut pairs, total) = g.min_cost_assignment(&left, &right);
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 6), (3, 7), (5, 11)]);
+        assert_eq!(total, 2);
+
+        // sparse random graphs still pair up as many as can be
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..300 {
+            let edges: Vec<(i32, i32, i32, i32)> = (0..rng.gen_range(0, 8))
+                .map(|i| {
+                    (
+                        i,
+                        rng.gen_range(0, 6),
+                        rng.gen_range(6, 12),
+                        rng.gen_range(-9, 50),
+                    )
+                })
+                .collect();
+            let g = build(U, 0..12, &edges);
+            let (pairs, _) = g.min_cost_assignment(&left, &right);
+            assert_eq!(pairs.len(), g.max_bipartite_matching().unwrap().len());
+        }
+    }
+}
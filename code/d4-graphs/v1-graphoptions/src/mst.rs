@@ -0,0 +1,175 @@
+use crate::dijkstra::Visit;
+use crate::disjoint::DisjointSet;
+use crate::graph::{Graph, GraphKind, Weighted};
+use crate::route::Route;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Edges of a minimum spanning tree, or forest if the graph is in pieces
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanningTree<EID> {
+    pub edges: Vec<EID>,
+    pub weight: i32,
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// Every edge at id whichever way it points, spanning trees ignore direction
+    fn touching<'a>(&'a self, id: &ID) -> Vec<(&'a EID, &'a E, &'a ID)> {
+        let mut res: Vec<_> = self.out_edges(id).collect();
+        if self.kind() == GraphKind::Directed {
+            res.extend(self.in_edges(id));
+        }
+        res
+    }
+
+    /// Cheapest edges first, skipping any that would close a loop
+    pub fn kruskal_mst(&self) -> SpanningTree<EID> {
+        let index: HashMap<&ID, usize> =
+            self.data.keys().enumerate().map(|(i, k)| (k, i)).collect();
+        let mut sorted: Vec<_> = self.edges.iter().collect();
+        sorted.sort_by_key(|(_, (e, _, _))| e.weight());
+        let mut sets = DisjointSet::new(index.len());
+        let mut res = SpanningTree {
+            edges: Vec::new(),
+            weight: 0,
+        };
+        for (eid, (e, from, to)) in sorted {
+            if sets.union(index[from], index[to]) {
+                res.edges.push(eid.clone());
+                res.weight += e.weight();
+            }
+        }
+        res
+    }
+
+    /// Grows a tree from one node by always taking the cheapest edge out of it
+    pub fn prim_mst(&self) -> SpanningTree<EID> {
+        let mut inside = HashSet::new();
+        let mut res = SpanningTree {
+            edges: Vec::new(),
+            weight: 0,
+        };
+        for root in self.data.keys() {
+            if !inside.insert(root) {
+                continue;
+            }
+            let mut heap = BinaryHeap::new();
+            let mut at = root;
+            loop {
+                for (eid, e, to) in self.touching(at) {
+                    if !inside.contains(to) {
+                        heap.push(Visit {
+                            dist: e.weight(),
+                            pos: (eid, to),
+                        });
+                    }
+                }
+                //cheapest edge still leading somewhere new
+                let next = loop {
+                    match heap.pop() {
+                        Some(Visit {
+                            dist,
+                            pos: (eid, to),
+                        }) if inside.insert(to) => break Some((dist, eid, to)),
+                        Some(_) => {}
+                        None => break None,
+                    }
+                };
+                match next {
+                    Some((dist, eid, to)) => {
+                        res.edges.push(eid.clone());
+                        res.weight += dist;
+                        at = to;
+                    }
+                    None => break,
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq + std::fmt::Debug, EID: Clone + Hash + Eq>
+    Graph<T, E, ID, EID>
+{
+    /// Visits nodes in the order a walk round the minimum spanning tree first
+    /// reaches them. With no negative weights this is never more than twice the best tour
+    pub fn mst_salesman(&self, start: ID) -> Option<Rc<Route<ID>>> {
+        let tree = self.kruskal_mst();
+        let mut adj: HashMap<&ID, Vec<&ID>> = HashMap::new();
+        for eid in &tree.edges {
+            let (_, a, b) = self.edge(eid)?;
+            adj.entry(a).or_default().push(b);
+            adj.entry(b).or_default().push(a);
+        }
+        let mut order = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![&start];
+        while let Some(n) = stack.pop() {
+            if !seen.insert(n) {
+                continue;
+            }
+            order.push(n.clone());
+            stack.extend(adj.get(n).into_iter().flatten().rev());
+        }
+        if order.len() != self.node_count() {
+            return None;
+        }
+        order.push(start.clone());
+        self.all_pairs_shortest_paths().ok()?.route(&order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{build, GraphKind};
+
+    const EDGES: [(char, char, char, i32); 11] = [
+        ('a', 'A', 'B', 7),
+        ('b', 'A', 'D', 5),
+        ('c', 'B', 'C', 8),
+        ('d', 'B', 'D', 9),
+        ('e', 'B', 'E', 7),
+        ('f', 'C', 'E', 5),
+        ('g', 'D', 'E', 15),
+        ('h', 'D', 'F', 6),
+        ('i', 'E', 'F', 8),
+        ('j', 'E', 'G', 9),
+        ('k', 'F', 'G', 11),
+    ];
+
+    #[test]
+    fn test_mst() {
+        for kind in &[GraphKind::Undirected, GraphKind::Directed] {
+            let g = build(*kind, "ABCDEFG".chars(), &EDGES);
+            let (k, p) = (g.kruskal_mst(), g.prim_mst());
+            assert_eq!((k.weight, p.weight), (39, 39));
+            let mut ke = k.edges.clone();
+            ke.sort();
+            assert_eq!(ke, vec!['a', 'b', 'e', 'f', 'h', 'j']);
+            assert_eq!(p.edges.len(), 6);
+        }
+
+        // two pieces give a forest
+        let mut g = build(GraphKind::Undirected, "ABCDEFG".chars(), &EDGES);
+        g.add_node('X', ()).unwrap();
+        g.add_node('Y', ()).unwrap();
+        g.add_edge('x', 'X', 'Y', 2).unwrap();
+        assert_eq!(g.kruskal_mst().weight, 41);
+        assert_eq!(g.prim_mst().edges.len(), 7);
+        assert!(g.mst_salesman('A').is_none());
+    }
+
+    #[test]
+    fn test_mst_salesman() {
+        let g = build(GraphKind::Undirected, "ABCDEFG".chars(), &EDGES);
+        let mst = g.kruskal_mst().weight;
+        let tour = g.mst_salesman('A').unwrap();
+        assert_eq!(tour.pos, 'A');
+        assert!("ABCDEFG".chars().all(|c| tour.contains(&c)));
+        // no tour can beat the tree, and this one is at most twice it
+        assert!(tour.len >= mst && tour.len <= 2 * mst, "{}", tour.len);
+        assert!(g.greedy_salesman('A').unwrap().len >= mst);
+    }
+}
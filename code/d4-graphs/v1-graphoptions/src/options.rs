@@ -1,3 +1,4 @@
+use crate::graph::GraphErr;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -8,6 +9,28 @@ pub struct EdgeListGraph<E, ID> {
     v: Vec<(E, ID, ID)>,
 }
 
+impl<E, ID> EdgeListGraph<E, ID> {
+    pub fn new() -> Self {
+        EdgeListGraph { v: Vec::new() }
+    }
+
+    /// Nodes exist only as edge ends, so any pair can be joined
+    pub fn add_edge(&mut self, from: ID, to: ID, e: E) {
+        self.v.push((e, from, to));
+    }
+
+    /// (edge, from, to) in the order added
+    pub fn edges(&self) -> &[(E, ID, ID)] {
+        &self.v
+    }
+}
+
+impl<E, ID> Default for EdgeListGraph<E, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 type Rcc<T> = Rc<RefCell<T>>;
 pub fn rcc<T>(t: T) -> Rcc<T> {
     Rc::new(RefCell::new(t))
@@ -23,11 +46,92 @@ pub struct RccNode<T> {
     edges: Vec<Weak<RefCell<RccNode<T>>>>,
 }
 
+impl<T> RccNode<T> {
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// The nodes this points to, skipping any since dropped
+    pub fn edges(&self) -> impl Iterator<Item = Rcc<RccNode<T>>> + '_ {
+        self.edges.iter().filter_map(|w| w.upgrade())
+    }
+}
+
+impl<T> RccGraph<T> {
+    pub fn new() -> Self {
+        RccGraph { nodes: Vec::new() }
+    }
+
+    /// The graph owns the node, edges only hold weak pointers to it
+    pub fn add_node(&mut self, data: T) -> Rcc<RccNode<T>> {
+        let n = rcc(RccNode {
+            data,
+            edges: Vec::new(),
+        });
+        self.nodes.push(n.clone());
+        n
+    }
+
+    pub fn add_edge(&mut self, from: &Rcc<RccNode<T>>, to: &Rcc<RccNode<T>>) {
+        from.borrow_mut().edges.push(Rc::downgrade(to));
+    }
+
+    pub fn nodes(&self) -> &[Rcc<RccNode<T>>] {
+        &self.nodes
+    }
+}
+
+impl<T> Default for RccGraph<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // MapBased
 pub struct MapGraph<T, E, ID: Hash> {
     mp: HashMap<ID, T>,
     edges: Vec<(E, ID, ID)>,
 }
+
+impl<T, E, ID: Hash + Eq> MapGraph<T, E, ID> {
+    pub fn new() -> Self {
+        MapGraph {
+            mp: HashMap::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, id: ID, dt: T) -> Result<(), GraphErr> {
+        if self.mp.contains_key(&id) {
+            return Err(GraphErr::DuplicateNode);
+        }
+        self.mp.insert(id, dt);
+        Ok(())
+    }
+
+    pub fn add_edge(&mut self, from: ID, to: ID, e: E) -> Result<(), GraphErr> {
+        if !self.mp.contains_key(&from) || !self.mp.contains_key(&to) {
+            return Err(GraphErr::MissingNode);
+        }
+        self.edges.push((e, from, to));
+        Ok(())
+    }
+
+    pub fn nodes(&self) -> &HashMap<ID, T> {
+        &self.mp
+    }
+
+    pub fn edges(&self) -> &[(E, ID, ID)] {
+        &self.edges
+    }
+}
+
+impl<T, E, ID: Hash + Eq> Default for MapGraph<T, E, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 //MapPointer
 
 pub struct MapPGraph<T, E, ID: Hash + Eq> {
@@ -35,6 +139,116 @@ pub struct MapPGraph<T, E, ID: Hash + Eq> {
     edges: HashMap<ID, (E, ID, ID)>,
 }
 
+impl<T, E, ID: Clone + Hash + Eq> MapPGraph<T, E, ID> {
+    pub fn new() -> Self {
+        MapPGraph {
+            data: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, id: ID, dt: T) -> Result<(), GraphErr> {
+        if self.data.contains_key(&id) {
+            return Err(GraphErr::DuplicateNode);
+        }
+        self.data.insert(id, (dt, Vec::new()));
+        Ok(())
+    }
+
+    /// Only the from node lists the edge
+    pub fn add_edge(&mut self, ed_id: ID, from: ID, to: ID, e: E) -> Result<(), GraphErr> {
+        if self.edges.contains_key(&ed_id) {
+            return Err(GraphErr::DuplicateEdge);
+        }
+        if !self.data.contains_key(&to) {
+            return Err(GraphErr::MissingNode);
+        }
+        let dt = self.data.get_mut(&from).ok_or(GraphErr::MissingNode)?;
+        dt.1.push(ed_id.clone());
+        self.edges.insert(ed_id, (e, from, to));
+        Ok(())
+    }
+
+    pub fn node(&self, id: &ID) -> Option<&T> {
+        self.data.get(id).map(|dt| &dt.0)
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = &ID> {
+        self.data.keys()
+    }
+
+    /// (edge id, edge, to) for each edge leaving id
+    pub fn out_edges<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a ID, &'a E, &'a ID)> + 'a {
+        let eids = self.data.get(id).map(|dt| &dt.1[..]).unwrap_or(&[]);
+        eids.iter().filter_map(move |eid| {
+            let (e, _, to) = self.edges.get(eid)?;
+            Some((eid, e, to))
+        })
+    }
+}
+
+impl<T, E, ID: Clone + Hash + Eq> Default for MapPGraph<T, E, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type RcEdge<E, ID> = Rc<(E, ID, ID)>;
+
 pub struct MapRcGraph<T, E, ID: Hash + Eq> {
-    data: HashMap<ID, (T, Vec<Rc<(E, ID, ID)>>)>,
+    data: HashMap<ID, (T, Vec<RcEdge<E, ID>>)>,
+}
+
+impl<T, E, ID: Clone + Hash + Eq> MapRcGraph<T, E, ID> {
+    pub fn new() -> Self {
+        MapRcGraph {
+            data: HashMap::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, id: ID, dt: T) -> Result<(), GraphErr> {
+        if self.data.contains_key(&id) {
+            return Err(GraphErr::DuplicateNode);
+        }
+        self.data.insert(id, (dt, Vec::new()));
+        Ok(())
+    }
+
+    /// Both ends share the one edge, from lists it first
+    pub fn add_edge(&mut self, from: ID, to: ID, e: E) -> Result<(), GraphErr> {
+        if !self.data.contains_key(&from) || !self.data.contains_key(&to) {
+            return Err(GraphErr::MissingNode);
+        }
+        let ed = Rc::new((e, from.clone(), to.clone()));
+        if from != to {
+            self.data.get_mut(&to).unwrap().1.push(ed.clone());
+        }
+        self.data.get_mut(&from).unwrap().1.push(ed);
+        Ok(())
+    }
+
+    pub fn node(&self, id: &ID) -> Option<&T> {
+        self.data.get(id).map(|dt| &dt.0)
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = &ID> {
+        self.data.keys()
+    }
+
+    /// (edge, to) for each edge leaving id
+    pub fn out_edges<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a E, &'a ID)> + 'a {
+        let id = id.clone();
+        self.data
+            .get(&id)
+            .into_iter()
+            .flat_map(|dt| dt.1.iter())
+            .filter(move |ed| ed.1 == id)
+            .map(|ed| (&ed.0, &ed.2))
+    }
+}
+
+impl<T, E, ID: Clone + Hash + Eq> Default for MapRcGraph<T, E, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
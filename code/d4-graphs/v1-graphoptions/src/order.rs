@@ -0,0 +1,230 @@
+use crate::graph::{CycleErr, Graph, GraphKind};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+impl<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// Every node after all those with edges into it (Kahn's algorithm).
+    /// Fails naming a cycle if there is one, so an undirected edge always fails
+    pub fn topological_sort(&self) -> Result<Vec<ID>, CycleErr<ID>> {
+        let mut waiting: HashMap<&ID, usize> = HashMap::new();
+        let mut ready = VecDeque::new();
+        for k in self.data.keys() {
+            match self.in_edges(k).count() {
+                0 => ready.push_back(k),
+                n => {
+                    waiting.insert(k, n);
+                }
+            }
+        }
+        let mut res = Vec::new();
+        while let Some(k) = ready.pop_front() {
+            res.push(k.clone());
+            for n in self.neighbors(k) {
+                let w = waiting.get_mut(n).expect("edge into a node already placed");
+                *w -= 1;
+                if *w == 0 {
+                    waiting.remove(n);
+                    ready.push_back(n);
+                }
+            }
+        }
+        // what is left each has an edge in from something else left,
+        // so walking those backwards must come round
+        let start = match waiting.keys().next() {
+            Some(k) => *k,
+            None => return Ok(res),
+        };
+        let mut seen = vec![start];
+        let mut pos = start;
+        loop {
+            pos = self
+                .in_edges(pos)
+                .map(|(_, _, f)| f)
+                .find(|f| waiting.contains_key(f))
+                .expect("left over node has a left over parent");
+            if let Some(i) = seen.iter().position(|s| *s == pos) {
+                let mut cycle: Vec<ID> = seen[i..].iter().map(|s| (*s).clone()).collect();
+                cycle.reverse();
+                return Err(CycleErr::Cycle(cycle));
+            }
+            seen.push(pos);
+        }
+    }
+
+    /// Groups where every node can reach every other (Tarjan's algorithm).
+    /// Each group comes before any group with edges into it
+    pub fn strongly_connected_components(&self) -> Vec<Vec<ID>> {
+        let mut index: HashMap<&ID, usize> = HashMap::new();
+        let mut low: HashMap<&ID, usize> = HashMap::new();
+        let mut on_stack = HashSet::new();
+        let mut stack = Vec::new();
+        let mut res = Vec::new();
+        for root in self.data.keys() {
+            if index.contains_key(root) {
+                continue;
+            }
+            index.insert(root, index.len());
+            low.insert(root, index[root]);
+            stack.push(root);
+            on_stack.insert(root);
+            let mut calls = vec![(root, self.neighbors(root))];
+            while let Some((v, exits)) = calls.last_mut() {
+                let v: &ID = v;
+                if let Some(w) = exits.next() {
+                    if !index.contains_key(w) {
+                        index.insert(w, index.len());
+                        low.insert(w, index[w]);
+                        stack.push(w);
+                        on_stack.insert(w);
+                        calls.push((w, self.neighbors(w)));
+                    } else if on_stack.contains(w) {
+                        let l = low[v].min(index[w]);
+                        low.insert(v, l);
+                    }
+                    continue;
+                }
+                calls.pop();
+                if let Some((u, _)) = calls.last() {
+                    let l = low[u].min(low[v]);
+                    low.insert(u, l);
+                }
+                if low[v] == index[v] {
+                    let mut comp = Vec::new();
+                    loop {
+                        let w = stack.pop().expect("v is on the stack");
+                        on_stack.remove(w);
+                        comp.push(w.clone());
+                        if w == v {
+                            break;
+                        }
+                    }
+                    res.push(comp);
+                }
+            }
+        }
+        res
+    }
+
+    /// One node per strongly connected component, holding its members, numbered
+    /// as strongly_connected_components gives them. Edges between components hold
+    /// the ids of the edges they stand for, the result has no cycles
+    pub fn condensation(&self) -> Graph<Vec<ID>, Vec<EID>, usize> {
+        let comps = self.strongly_connected_components();
+        let mut which = HashMap::new();
+        for (i, c) in comps.iter().enumerate() {
+            for n in c {
+                which.insert(n.clone(), i);
+            }
+        }
+        let mut links: HashMap<(usize, usize), Vec<EID>> = HashMap::new();
+        for (i, c) in comps.iter().enumerate() {
+            for n in c {
+                for (eid, _, to) in self.out_edges(n) {
+                    let j = which[to];
+                    if i != j {
+                        links.entry((i, j)).or_default().push(eid.clone());
+                    }
+                }
+            }
+        }
+        let mut res = Graph::new(GraphKind::Directed);
+        for (i, c) in comps.into_iter().enumerate() {
+            res.add_node(i, c).expect("component numbers are unique");
+        }
+        for (e, ((i, j), eids)) in links.into_iter().enumerate() {
+            res.add_edge(e, i, j, eids).expect("both components added");
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{build, CycleErr, GraphKind};
+
+    #[test]
+    fn test_topological_sort() {
+        let deps = [
+            (0, "std", "io", ()),
+            (1, "std", "fmt", ()),
+            (2, "fmt", "log", ()),
+            (3, "io", "log", ()),
+            (4, "log", "app", ()),
+            (5, "io", "app", ()),
+        ];
+        let g = build(
+            GraphKind::Directed,
+            vec!["std", "io", "fmt", "log", "app"],
+            &deps,
+        );
+        let order = g.topological_sort().unwrap();
+        assert_eq!(order.len(), 5);
+        let at = |n: &str| order.iter().position(|x| *x == n).unwrap();
+        for (_, a, b, _) in &deps {
+            assert!(at(a) < at(b), "{} before {}", a, b);
+        }
+
+        let g = build(
+            GraphKind::Directed,
+            "abcde".chars(),
+            &[
+                (0, 'a', 'b', ()),
+                (1, 'b', 'c', ()),
+                (2, 'c', 'd', ()),
+                (3, 'd', 'b', ()),
+                (4, 'd', 'e', ()),
+            ],
+        );
+        match g.topological_sort() {
+            Err(CycleErr::Cycle(mut c)) => {
+                // in edge order, starting wherever
+                let i = c.iter().position(|x| *x == 'b').unwrap();
+                c.rotate_left(i);
+                assert_eq!(c, vec!['b', 'c', 'd']);
+            }
+            r => panic!("expected cycle, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_scc_condensation() {
+        let g = build(
+            GraphKind::Directed,
+            "abcdef".chars(),
+            &[
+                (0, 'a', 'b', ()),
+                (1, 'b', 'c', ()),
+                (2, 'c', 'a', ()),
+                (3, 'b', 'd', ()),
+                (4, 'd', 'e', ()),
+                (5, 'e', 'd', ()),
+                (6, 'e', 'f', ()),
+                (7, 'c', 'f', ()),
+                (8, 'f', 'f', ()),
+            ],
+        );
+        let comps = g.strongly_connected_components();
+        let mut sorted: Vec<Vec<char>> = comps
+            .iter()
+            .map(|c| {
+                let mut c = c.clone();
+                c.sort();
+                c
+            })
+            .collect();
+        sorted.sort();
+        assert_eq!(sorted, vec![vec!['a', 'b', 'c'], vec!['d', 'e'], vec!['f']]);
+        // sinks first
+        assert_eq!(comps[0], vec!['f']);
+
+        let dag = g.condensation();
+        assert_eq!((dag.node_count(), dag.edge_count()), (3, 3));
+        let order = dag.topological_sort().unwrap();
+        assert_eq!(dag.node(&order[0]).unwrap().len(), 3);
+        assert_eq!(order[2], 0);
+        let abc = order[0];
+        let mut into_f: Vec<usize> = dag.in_edges(&0).map(|(_, _, f)| *f).collect();
+        into_f.sort();
+        assert!(into_f.contains(&abc) && into_f.len() == 2);
+    }
+}
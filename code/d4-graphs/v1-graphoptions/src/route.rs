@@ -0,0 +1,224 @@
+use crate::dijkstra::Visit;
+use crate::graph::{Graph, Weighted};
+use rand::prelude::*;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct Route<ID> {
+    pub pos: ID,
+    pub path: Option<Rc<Route<ID>>>,
+    pub len: i32,
+}
+
+impl<ID: Eq> Route<ID> {
+    pub fn start_rc(pos: ID) -> Rc<Self> {
+        Rc::new(Route {
+            pos,
+            path: None,
+            len: 0,
+        })
+    }
+
+    pub fn contains(&self, id: &ID) -> bool {
+        if self.pos == *id {
+            return true;
+        }
+        match self.path {
+            Some(ref p) => p.contains(id),
+            None => false,
+        }
+    }
+}
+
+impl<ID: std::fmt::Debug> fmt::Display for Route<ID> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref p) = self.path {
+            write!(f, "{}-{}-", p, self.len)?;
+        }
+        write!(f, "{:?}", self.pos)
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq + fmt::Debug, EID: Clone + Hash + Eq>
+    Graph<T, E, ID, EID>
+{
+    pub fn shortest_path(&self, from: ID, to: ID) -> Option<Rc<Route<ID>>> {
+        self.shortest_path_r(Route::start_rc(from), to)
+    }
+
+    pub fn shortest_path_r(&self, sofar: Rc<Route<ID>>, to: ID) -> Option<Rc<Route<ID>>> {
+        let mut toset = HashSet::new();
+        toset.insert(to);
+        self.closest(sofar, &toset)
+    }
+
+    /// Nearest of to along from, None if none can be reached or a negative weight can
+    pub fn closest(&self, from: Rc<Route<ID>>, to: &HashSet<ID>) -> Option<Rc<Route<ID>>> {
+        //a visited node is taken as done, which a negative edge could undercut
+        if self.negative_reachable(&from.pos) {
+            return None;
+        }
+        let mut visited = HashSet::new();
+        let mut routes = BinaryHeap::new();
+        routes.push(Visit {
+            dist: from.len,
+            pos: from,
+        });
+        loop {
+            let c_route = routes.pop()?.pos;
+            //To win must be the top route
+            if to.contains(&c_route.pos) {
+                return Some(c_route);
+            }
+            if visited.contains(&c_route.pos) {
+                //This is here because if we do it at add, that means we won't
+                //add a shorter route if we find one
+                continue;
+            }
+            visited.insert(c_route.pos.clone());
+            // only along edges we may walk
+            for (_, edge, npos) in self.out_edges(&c_route.pos) {
+                let nlen = c_route.len + edge.weight();
+                let nroute = Rc::new(Route {
+                    pos: npos.clone(),
+                    len: nlen,
+                    path: Some(c_route.clone()),
+                });
+                routes.push(Visit {
+                    dist: nlen,
+                    pos: nroute,
+                });
+            }
+        }
+    }
+
+    pub fn greedy_salesman(&self, start: ID) -> Option<Rc<Route<ID>>> {
+        let m = self.all_pairs_shortest_paths().ok()?;
+        let mut to_visit: HashSet<ID> = self.data.keys().cloned().collect();
+        to_visit.remove(&start);
+        let mut route = Route::start_rc(start.clone());
+        while !to_visit.is_empty() {
+            let next = to_visit
+                .iter()
+                .filter_map(|t| Some((m.dist(&route.pos, t)?, t)))
+                .min_by_key(|(d, _)| *d)?
+                .1
+                .clone();
+            route = m.extend(route, &next)?;
+            to_visit.retain(|t| !route.contains(t));
+        }
+        route = m.extend(route, &start)?;
+        Some(route)
+    }
+
+    /// One search per leg. For many routes over the same graph build
+    /// all_pairs_shortest_paths once and use DistMatrix::route instead
+    pub fn complete_path(&self, path: &[ID]) -> Option<Rc<Route<ID>>> {
+        if path.len() < 2 {
+            return None;
+        }
+        let mut route = Route::start_rc(path[0].clone());
+        for pos in &path[1..path.len() - 1] {
+            if !route.contains(pos) {
+                route = self.shortest_path_r(route, pos.clone())?;
+            }
+        }
+        //include final elem without checking
+        route = self.shortest_path_r(route, path[path.len() - 1].clone())?;
+
+        Some(route)
+    }
+
+    pub fn iter_salesman(&self, start: ID) -> Option<Rc<Route<ID>>> {
+        let mut bpath: Vec<ID> = self.data.keys().cloned().collect();
+        bpath.shuffle(&mut rand::thread_rng());
+        for n in 0..bpath.len() {
+            if bpath[n] == start {
+                bpath.swap(0, n);
+                break;
+            }
+        }
+        bpath.push(start); //start and finish = A
+
+        //every leg of every try comes from the one matrix
+        let m = self.all_pairs_shortest_paths().ok()?;
+        let mut broute = m.route(&bpath)?;
+        let mut no_imp = 0;
+        loop {
+            let mut p2 = bpath.clone();
+            let sa = (rand::random::<usize>() % (p2.len() - 2)) + 1;
+            let sb = (rand::random::<usize>() % (p2.len() - 2)) + 1;
+            p2.swap(sa, sb);
+            let r2 = m.route(&p2)?;
+            if r2.len < broute.len {
+                bpath = p2;
+                broute = r2;
+                no_imp = 0;
+            }
+            no_imp += 1;
+            if no_imp == 50 {
+                return Some(broute);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphKind};
+
+    #[test]
+    fn test_one_way_roads() {
+        for kind in &[GraphKind::Directed, GraphKind::Undirected] {
+            let mut g = Graph::new(*kind);
+            for x in "ABC".chars() {
+                g.add_node(x, ()).unwrap();
+            }
+            g.add_edge('a', 'A', 'B', 1).unwrap();
+            g.add_edge('b', 'B', 'C', 1).unwrap();
+            g.add_edge('c', 'C', 'A', 5).unwrap();
+            let back = g.shortest_path('B', 'A').unwrap();
+            match kind {
+                // has to go round through C
+                GraphKind::Directed => assert_eq!(back.len, 6),
+                GraphKind::Undirected => assert_eq!(back.len, 1),
+            }
+            assert!(g.shortest_path('A', 'C').unwrap().contains(&'B'));
+
+            // leg by leg agrees with the all pairs matrix
+            let m = g.all_pairs_shortest_paths().unwrap();
+            for path in &[
+                vec!['A', 'C'],
+                vec!['C', 'B', 'A'],
+                vec!['B', 'A', 'C', 'B'],
+            ] {
+                let legs = g.complete_path(path).unwrap();
+                assert_eq!(legs.len, m.route(path).unwrap().len);
+                assert!(path.iter().all(|p| legs.contains(p)));
+            }
+            assert!(g.complete_path(&['A']).is_none());
+        }
+    }
+
+    #[test]
+    fn test_negative_weight() {
+        let mut g = Graph::new(GraphKind::Directed);
+        for x in "ABCD".chars() {
+            g.add_node(x, ()).unwrap();
+        }
+        g.add_edge('a', 'A', 'B', 2).unwrap();
+        g.add_edge('b', 'A', 'C', 5).unwrap();
+        g.add_edge('d', 'D', 'A', -1).unwrap();
+        // D can't be reached from A, so its edge does no harm
+        assert_eq!(g.shortest_path('A', 'B').unwrap().len, 2);
+
+        // B is found at 2, but the way through C is 1
+        g.add_edge('c', 'C', 'B', -4).unwrap();
+        assert!(g.shortest_path('A', 'B').is_none());
+        assert!(g.complete_path(&['A', 'C', 'B']).is_none());
+        assert_eq!(g.bellman_ford(&'A').unwrap().dist(&'B'), Some(1));
+    }
+}
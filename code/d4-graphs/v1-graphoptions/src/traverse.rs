@@ -0,0 +1,398 @@
+use crate::dijkstra::{self, ShortestPathTree};
+use crate::graph::{Graph, GraphErr, GraphKind, Weighted};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// What the traversals need from a graph, so they run unchanged on Graph
+/// (whatever its edges hold) and on CsrGraph
+pub trait Walk {
+    type Id: Clone + Hash + Eq;
+
+    fn kind(&self) -> GraphKind;
+
+    fn node_ids<'a>(&'a self) -> impl Iterator<Item = &'a Self::Id> + 'a;
+
+    /// The id as stored, so steps can borrow from the graph not the caller
+    fn key<'a>(&'a self, id: &Self::Id) -> Option<&'a Self::Id>;
+
+    /// Nodes reachable from id in one step
+    fn neighbors<'a>(&'a self, id: &Self::Id) -> impl Iterator<Item = &'a Self::Id> + 'a;
+
+    fn bfs<'a>(&'a self, start: &Self::Id) -> Bfs<'a, Self> {
+        let mut res = Bfs {
+            g: self,
+            queue: VecDeque::new(),
+            seen: HashSet::new(),
+        };
+        if let Some(k) = self.key(start) {
+            res.seen.insert(k);
+            res.queue.push_back(Step {
+                node: k,
+                depth: 0,
+                parent: None,
+            });
+        }
+        res
+    }
+
+    fn dfs<'a>(&'a self, start: &Self::Id) -> Dfs<'a, Self> {
+        let stack = self
+            .key(start)
+            .map(|k| Step {
+                node: k,
+                depth: 0,
+                parent: None,
+            })
+            .into_iter()
+            .collect();
+        Dfs {
+            g: self,
+            stack,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Everything start can reach, start included
+    fn reachable(&self, start: &Self::Id) -> HashSet<Self::Id> {
+        self.bfs(start).map(|s| s.node.clone()).collect()
+    }
+}
+
+/// Walk with a weight on every edge, what Dijkstra needs
+pub trait Traverse: Walk {
+    type EdgeId: Clone;
+
+    /// (edge id, weight, far end) for each edge that can be walked away from id
+    fn exits<'a>(
+        &'a self,
+        id: &Self::Id,
+    ) -> impl Iterator<Item = (&'a Self::EdgeId, i32, &'a Self::Id)> + 'a;
+
+    /// Shortest paths from source to every node it can reach.
+    /// All weights must be 0 or more, see bellman_ford otherwise
+    fn dijkstra(
+        &self,
+        source: &Self::Id,
+    ) -> Result<ShortestPathTree<Self::Id, Self::EdgeId>, GraphErr> {
+        dijkstra::dijkstra(self, source)
+    }
+}
+
+impl<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Walk for Graph<T, E, ID, EID> {
+    type Id = ID;
+
+    fn kind(&self) -> GraphKind {
+        Graph::kind(self)
+    }
+
+    fn node_ids<'a>(&'a self) -> impl Iterator<Item = &'a ID> + 'a {
+        self.data.keys()
+    }
+
+    fn key<'a>(&'a self, id: &ID) -> Option<&'a ID> {
+        Graph::key(self, id)
+    }
+
+    fn neighbors<'a>(&'a self, id: &ID) -> impl Iterator<Item = &'a ID> + 'a {
+        Graph::neighbors(self, id)
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Traverse
+    for Graph<T, E, ID, EID>
+{
+    type EdgeId = EID;
+
+    fn exits<'a>(&'a self, id: &ID) -> impl Iterator<Item = (&'a EID, i32, &'a ID)> + 'a {
+        self.out_edges(id).map(|(eid, e, n)| (eid, e.weight(), n))
+    }
+}
+
+/// A node as reached by a traversal, how deep and from where
+#[derive(Debug, PartialEq)]
+pub struct Step<'a, ID> {
+    pub node: &'a ID,
+    pub depth: usize,
+    pub parent: Option<&'a ID>,
+}
+
+pub struct Bfs<'a, G: Walk + ?Sized> {
+    g: &'a G,
+    queue: VecDeque<Step<'a, G::Id>>,
+    seen: HashSet<&'a G::Id>,
+}
+
+impl<'a, G: Walk + ?Sized> Iterator for Bfs<'a, G> {
+    type Item = Step<'a, G::Id>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.queue.pop_front()?;
+        for n in self.g.neighbors(step.node) {
+            if self.seen.insert(n) {
+                self.queue.push_back(Step {
+                    node: n,
+                    depth: step.depth + 1,
+                    parent: Some(step.node),
+                });
+            }
+        }
+        Some(step)
+    }
+}
+
+/// Preorder, each node given when first reached
+pub struct Dfs<'a, G: Walk + ?Sized> {
+    g: &'a G,
+    stack: Vec<Step<'a, G::Id>>,
+    seen: HashSet<&'a G::Id>,
+}
+
+impl<'a, G: Walk + ?Sized> Iterator for Dfs<'a, G> {
+    type Item = Step<'a, G::Id>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let step = self.stack.pop()?;
+            if !self.seen.insert(step.node) {
+                continue;
+            }
+            //reversed so the first edge is the first gone down
+            let ns: Vec<&G::Id> = self.g.neighbors(step.node).collect();
+            for n in ns.into_iter().rev() {
+                if !self.seen.contains(n) {
+                    self.stack.push(Step {
+                        node: n,
+                        depth: step.depth + 1,
+                        parent: Some(step.node),
+                    });
+                }
+            }
+            return Some(step);
+        }
+    }
+}
+
+/// Events from dfs_visit, each does nothing unless overridden
+pub trait DfsVisitor<ID, EID = ID> {
+    fn discover(&mut self, _node: &ID, _depth: usize) {}
+    /// Every node reachable from node has been discovered
+    fn finish(&mut self, _node: &ID) {}
+    /// The edge by which to was discovered
+    fn tree_edge(&mut self, _eid: &EID, _from: &ID, _to: &ID) {}
+    /// To is still being explored, so this edge closes a cycle
+    fn back_edge(&mut self, _eid: &EID, _from: &ID, _to: &ID) {}
+    /// To is finished. Forward edges, to a descendant already reached another way, land here too
+    fn cross_edge(&mut self, _eid: &EID, _from: &ID, _to: &ID) {}
+}
+
+#[derive(PartialEq)]
+enum Colour {
+    Open,
+    Done,
+}
+
+impl<T, E, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// The key as stored, so steps can borrow from the graph not the caller
+    fn key<'a>(&'a self, id: &ID) -> Option<&'a ID> {
+        self.data.get_key_value(id).map(|(k, _)| k)
+    }
+
+    pub fn bfs<'a>(&'a self, start: &ID) -> Bfs<'a, Self> {
+        Walk::bfs(self, start)
+    }
+
+    pub fn dfs<'a>(&'a self, start: &ID) -> Dfs<'a, Self> {
+        Walk::dfs(self, start)
+    }
+
+    /// Everything start can reach, start included
+    pub fn reachable(&self, start: &ID) -> HashSet<ID> {
+        Walk::reachable(self, start)
+    }
+
+    fn dfs_from<'a, V: DfsVisitor<ID, EID>>(
+        &'a self,
+        start: &'a ID,
+        colour: &mut HashMap<&'a ID, Colour>,
+        v: &mut V,
+    ) {
+        //node, edge used to get there, and the edges left to look at
+        let mut stack = vec![(start, None, self.out_edges(start))];
+        colour.insert(start, Colour::Open);
+        v.discover(start, 0);
+        while let Some((node, arrived, exits)) = stack.last_mut() {
+            let node: &ID = node;
+            let (eid, _, to) = match exits.next() {
+                Some(x) => x,
+                None => {
+                    colour.insert(node, Colour::Done);
+                    v.finish(node);
+                    stack.pop();
+                    continue;
+                }
+            };
+            //in an undirected graph the way we came in is no cycle
+            if *arrived == Some(eid) {
+                continue;
+            }
+            match colour.get(to) {
+                None => {
+                    v.tree_edge(eid, node, to);
+                    colour.insert(to, Colour::Open);
+                    v.discover(to, stack.len());
+                    stack.push((to, Some(eid), self.out_edges(to)));
+                }
+                Some(Colour::Open) => v.back_edge(eid, node, to),
+                Some(Colour::Done) => v.cross_edge(eid, node, to),
+            }
+        }
+    }
+
+    /// Depth first from start, telling v about every node and edge on the way
+    pub fn dfs_visit<V: DfsVisitor<ID, EID>>(&self, start: &ID, v: &mut V) {
+        if let Some(k) = self.key(start) {
+            self.dfs_from(k, &mut HashMap::new(), v);
+        }
+    }
+
+    /// dfs_visit from each node not yet reached, so every node is seen
+    pub fn dfs_visit_all<V: DfsVisitor<ID, EID>>(&self, v: &mut V) {
+        let mut colour = HashMap::new();
+        for k in self.data.keys() {
+            if !colour.contains_key(k) {
+                self.dfs_from(k, &mut colour, v);
+            }
+        }
+    }
+
+    pub fn has_cycle(&self) -> bool {
+        struct Backs(bool);
+        impl<ID, EID> DfsVisitor<ID, EID> for Backs {
+            fn back_edge(&mut self, _: &EID, _: &ID, _: &ID) {
+                self.0 = true;
+            }
+        }
+        let mut b = Backs(false);
+        self.dfs_visit_all(&mut b);
+        b.0
+    }
+
+    /// Groups of nodes joined by edges, ignoring which way the edges go
+    pub fn connected_components(&self) -> Vec<Vec<ID>> {
+        let mut seen = HashSet::new();
+        let mut res = Vec::new();
+        for k in self.data.keys() {
+            if seen.contains(k) {
+                continue;
+            }
+            let mut comp = vec![k.clone()];
+            seen.insert(k);
+            let mut i = 0;
+            while i < comp.len() {
+                let n = comp[i].clone();
+                let near = self.out_edges(&n).chain(self.in_edges(&n));
+                for (_, _, m) in near {
+                    if seen.insert(m) {
+                        comp.push(m.clone());
+                    }
+                }
+                i += 1;
+            }
+            res.push(comp);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::GraphKind;
+
+    fn tree(kind: GraphKind) -> Graph<(), (), i32> {
+        // 0 -> 1 -> 3, 0 -> 2 -> 3, 3 -> 4, and 5 on its own
+        let mut g = Graph::new(kind);
+        for x in 0..6 {
+            g.add_node(x, ()).unwrap();
+        }
+        for (e, a, b) in &[(0, 0, 1), (1, 0, 2), (2, 1, 3), (3, 2, 3), (4, 3, 4)] {
+            g.add_edge(*e, *a, *b, ()).unwrap();
+        }
+        g
+    }
+
+    #[test]
+    fn test_bfs_dfs() {
+        let g = tree(GraphKind::Directed);
+        let b: Vec<_> = g
+            .bfs(&0)
+            .map(|s| (*s.node, s.depth, s.parent.cloned()))
+            .collect();
+        assert_eq!(
+            b,
+            vec![
+                (0, 0, None),
+                (1, 1, Some(0)),
+                (2, 1, Some(0)),
+                (3, 2, Some(1)),
+                (4, 3, Some(3))
+            ]
+        );
+        let d: Vec<_> = g.dfs(&0).map(|s| (*s.node, s.depth)).collect();
+        assert_eq!(d, vec![(0, 0), (1, 1), (3, 2), (4, 3), (2, 1)]);
+        assert_eq!(g.bfs(&4).count(), 1);
+        assert_eq!(g.dfs(&9).count(), 0);
+        assert_eq!(g.reachable(&2).len(), 3);
+
+        // edges that are not Weighted can still be walked
+        let mut named: Graph<(), &str, i32> = Graph::new(GraphKind::Directed);
+        for x in 0..3 {
+            named.add_node(x, ()).unwrap();
+        }
+        named.add_edge(0, 0, 1, "high street").unwrap();
+        named.add_edge(1, 1, 2, "bridge").unwrap();
+        assert_eq!(named.bfs(&0).count(), 3);
+        assert_eq!(named.dfs(&1).count(), 2);
+        assert_eq!(named.reachable(&2).len(), 1);
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+    impl DfsVisitor<i32> for Log {
+        fn discover(&mut self, n: &i32, d: usize) {
+            self.0.push(format!("d{}@{}", n, d));
+        }
+        fn finish(&mut self, n: &i32) {
+            self.0.push(format!("f{}", n));
+        }
+        fn tree_edge(&mut self, _: &i32, a: &i32, b: &i32) {
+            self.0.push(format!("t{}{}", a, b));
+        }
+        fn back_edge(&mut self, _: &i32, a: &i32, b: &i32) {
+            self.0.push(format!("b{}{}", a, b));
+        }
+        fn cross_edge(&mut self, _: &i32, a: &i32, b: &i32) {
+            self.0.push(format!("c{}{}", a, b));
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut g = tree(GraphKind::Directed);
+        let mut log = Log::default();
+        g.dfs_visit(&0, &mut log);
+        assert_eq!(
+            log.0.join(" "),
+            "d0@0 t01 d1@1 t13 d3@2 t34 d4@3 f4 f3 f1 t02 d2@1 c23 f2 f0"
+        );
+        assert!(!g.has_cycle());
+        g.add_edge(5, 4, 1, ()).unwrap();
+        assert!(g.has_cycle());
+
+        // undirected, going back along the tree edge is not a cycle but 0 1 3 2 is
+        let u = tree(GraphKind::Undirected);
+        assert!(u.has_cycle());
+        let mut comps: Vec<usize> = u.connected_components().iter().map(|c| c.len()).collect();
+        comps.sort();
+        assert_eq!(comps, vec![1, 5]);
+        assert_eq!(g.connected_components().len(), 2);
+    }
+}
@@ -0,0 +1,401 @@
+//! Travelling salesman over the shortest path distances between nodes.
+//! Solvers fix the first node of the tour and work on indices into a cost matrix.
+use crate::allpairs::DistMatrix;
+use crate::graph::{Graph, Weighted};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::hash::Hash;
+
+/// Held-Karp takes 2^n·n² time and 2^n·n memory, more nodes than this is too slow
+pub const MAX_EXACT: usize = 20;
+
+/// A closed tour, order does not repeat the start at the end, len includes getting back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour<ID> {
+    pub order: Vec<ID>,
+    pub len: i32,
+}
+
+impl<ID: fmt::Debug> fmt::Display for Tour<ID> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for id in &self.order {
+            write!(f, "{:?}-", id)?;
+        }
+        if let Some(s) = self.order.first() {
+            write!(f, "{:?}", s)?;
+        }
+        write!(f, " ({})", self.len)
+    }
+}
+
+/// Settings for simulated annealing
+#[derive(Debug, Clone)]
+pub struct Anneal {
+    pub seed: u64,
+    pub start_temp: f64,
+    /// Temperature is multiplied by this after every step
+    pub cooling: f64,
+    pub steps: usize,
+}
+
+impl Default for Anneal {
+    fn default() -> Self {
+        Anneal {
+            seed: 1,
+            start_temp: 100.0,
+            cooling: 0.9995,
+            steps: 20_000,
+        }
+    }
+}
+
+pub struct Tsp<ID> {
+    ids: Vec<ID>,
+    d: Vec<Vec<i32>>,
+    //2-opt can skip re-costing a reversed segment if every edge costs the same both ways
+    symmetric: bool,
+}
+
+impl<ID: Clone + Hash + Eq> Tsp<ID> {
+    /// None if some node cannot reach another
+    pub fn new(m: &DistMatrix<ID>) -> Option<Self> {
+        let ids = m.ids().to_vec();
+        let mut d = Vec::new();
+        for a in &ids {
+            d.push(
+                ids.iter()
+                    .map(|b| m.dist(a, b))
+                    .collect::<Option<Vec<i32>>>()?,
+            );
+        }
+        let n = ids.len();
+        let symmetric = (0..n).all(|i| (0..i).all(|j| d[i][j] == d[j][i]));
+        Some(Tsp { ids, d, symmetric })
+    }
+
+    fn cost(&self, t: &[usize]) -> i32 {
+        (0..t.len())
+            .map(|i| self.d[t[i]][t[(i + 1) % t.len()]])
+            .sum()
+    }
+
+    fn tour(&self, t: &[usize]) -> Tour<ID> {
+        Tour {
+            order: t.iter().map(|i| self.ids[*i].clone()).collect(),
+            len: self.cost(t),
+        }
+    }
+
+    fn indices(&self, tour: &Tour<ID>) -> Vec<usize> {
+        tour.order
+            .iter()
+            .map(|id| {
+                self.ids
+                    .iter()
+                    .position(|x| x == id)
+                    .expect("tour of other nodes")
+            })
+            .collect()
+    }
+
+    /// Always on to the closest node not yet visited
+    pub fn nearest_neighbour(&self, start: &ID) -> Option<Tour<ID>> {
+        let mut at = self.ids.iter().position(|x| x == start)?;
+        let mut left: Vec<usize> = (0..self.ids.len()).filter(|i| *i != at).collect();
+        let mut t = vec![at];
+        while !left.is_empty() {
+            let (li, _) = left
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, n)| self.d[at][**n])?;
+            at = left.swap_remove(li);
+            t.push(at);
+        }
+        Some(self.tour(&t))
+    }
+
+    /// Change in cost from reversing t[i..=j]
+    fn reverse_delta(&self, t: &[usize], i: usize, j: usize) -> i32 {
+        let n = t.len();
+        let (a, b) = (t[i - 1], t[(j + 1) % n]);
+        let mut res = self.d[a][t[j]] + self.d[t[i]][b] - self.d[a][t[i]] - self.d[t[j]][b];
+        if !self.symmetric {
+            for k in i..j {
+                res += self.d[t[k + 1]][t[k]] - self.d[t[k]][t[k + 1]];
+            }
+        }
+        res
+    }
+
+    /// One pass of the best improving reversals, true if anything changed
+    fn two_opt_pass(&self, t: &mut [usize]) -> bool {
+        let n = t.len();
+        let mut improved = false;
+        for i in 1..n.saturating_sub(1) {
+            for j in i + 1..n {
+                if self.reverse_delta(t, i, j) < 0 {
+                    t[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+        improved
+    }
+
+    /// Moves runs of 1 to 3 nodes elsewhere in the tour, keeping their direction
+    fn or_opt_pass(&self, t: &mut Vec<usize>) -> bool {
+        let n = t.len();
+        let mut improved = false;
+        for k in 1..=3.min(n.saturating_sub(2)) {
+            let mut i = 1;
+            while i + k <= n {
+                let (p, s0, se, q) = (t[i - 1], t[i], t[i + k - 1], t[(i + k) % n]);
+                let gain = self.d[p][s0] + self.d[se][q] - self.d[p][q];
+                let mut rest: Vec<usize> = t[..i].to_vec();
+                rest.extend(&t[i + k..]);
+                //best gap in what is left to put the run back in
+                let best = (0..rest.len())
+                    .map(|g| {
+                        let (a, b) = (rest[g], rest[(g + 1) % rest.len()]);
+                        (self.d[a][s0] + self.d[se][b] - self.d[a][b], g)
+                    })
+                    .min();
+                match best {
+                    Some((add, g)) if add < gain => {
+                        let seg = t[i..i + k].to_vec();
+                        rest.splice(g + 1..g + 1, seg);
+                        *t = rest;
+                        improved = true;
+                    }
+                    _ => i += 1,
+                }
+            }
+        }
+        improved
+    }
+
+    /// Reverses stretches of the tour until no reversal makes it shorter
+    pub fn two_opt(&self, tour: &Tour<ID>) -> Tour<ID> {
+        let mut t = self.indices(tour);
+        while self.two_opt_pass(&mut t) {}
+        self.tour(&t)
+    }
+
+    pub fn or_opt(&self, tour: &Tour<ID>) -> Tour<ID> {
+        let mut t = self.indices(tour);
+        while self.or_opt_pass(&mut t) {}
+        self.tour(&t)
+    }
+
+    /// 2-opt and Or-opt in turn until neither helps
+    pub fn local_search(&self, tour: &Tour<ID>) -> Tour<ID> {
+        let mut t = self.indices(tour);
+        loop {
+            let a = self.two_opt_pass(&mut t);
+            let b = self.or_opt_pass(&mut t);
+            if !(a || b) {
+                return self.tour(&t);
+            }
+        }
+    }
+
+    /// Random reversals, taking worse ones too while hot so it can climb out of
+    /// a local best. The same settings always give the same tour
+    pub fn anneal(&self, tour: &Tour<ID>, a: &Anneal) -> Tour<ID> {
+        let mut rng = StdRng::seed_from_u64(a.seed);
+        let mut t = self.indices(tour);
+        let n = t.len();
+        let mut best = t.clone();
+        let (mut len, mut best_len) = (self.cost(&t), self.cost(&t));
+        if n < 4 {
+            return self.tour(&t);
+        }
+        let mut temp = a.start_temp;
+        for _ in 0..a.steps {
+            let i = rng.gen_range(1, n - 1);
+            let j = rng.gen_range(i + 1, n);
+            let delta = self.reverse_delta(&t, i, j);
+            if delta < 0 || rng.gen::<f64>() < (-delta as f64 / temp).exp() {
+                t[i..=j].reverse();
+                len += delta;
+                if len < best_len {
+                    best_len = len;
+                    best = t.clone();
+                }
+            }
+            temp *= a.cooling;
+        }
+        self.tour(&best)
+    }
+
+    /// The best tour for certain, by dynamic programming over subsets.
+    /// None past MAX_EXACT nodes
+    pub fn held_karp(&self, start: &ID) -> Option<Tour<ID>> {
+        let n = self.ids.len();
+        let s = self.ids.iter().position(|x| x == start)?;
+        if n > MAX_EXACT {
+            return None;
+        }
+        if n == 1 {
+            return Some(self.tour(&[s]));
+        }
+        let others: Vec<usize> = (0..n).filter(|i| *i != s).collect();
+        let m = others.len();
+        let full = 1usize << m;
+        //best[mask * m + j]: shortest from start through mask ending at others[j]
+        let mut best = vec![i32::MAX; full * m];
+        let mut from = vec![0u8; full * m];
+        for j in 0..m {
+            best[(1 << j) * m + j] = self.d[s][others[j]];
+        }
+        for mask in 1..full {
+            for j in 0..m {
+                let cur = best[mask * m + j];
+                if mask & (1 << j) == 0 || cur == i32::MAX {
+                    continue;
+                }
+                for k in 0..m {
+                    if mask & (1 << k) != 0 {
+                        continue;
+                    }
+                    let nm = mask | (1 << k);
+                    let nd = cur + self.d[others[j]][others[k]];
+                    if nd < best[nm * m + k] {
+                        best[nm * m + k] = nd;
+                        from[nm * m + k] = j as u8;
+                    }
+                }
+            }
+        }
+        let last = full - 1;
+        let mut j = (0..m).min_by_key(|j| best[last * m + j] + self.d[others[*j]][s])?;
+        let mut t = Vec::new();
+        let mut mask = last;
+        loop {
+            t.push(others[j]);
+            let pj = from[mask * m + j] as usize;
+            mask &= !(1 << j);
+            if mask == 0 {
+                break;
+            }
+            j = pj;
+        }
+        t.push(s);
+        t.reverse();
+        Some(self.tour(&t))
+    }
+}
+
+impl<T, E: Weighted, ID: Clone + Hash + Eq, EID: Clone + Hash + Eq> Graph<T, E, ID, EID> {
+    /// None if there is a negative cycle or some node cannot reach another
+    pub fn tsp(&self) -> Option<Tsp<ID>> {
+        Tsp::new(&self.all_pairs_shortest_paths().ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::GraphKind;
+
+    /// Complete graph of random points, cost the rounded distance
+    fn cities(n: usize, seed: u64, kind: GraphKind) -> Graph<(), i32, usize> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let pts: Vec<(f64, f64)> = (0..n)
+            .map(|_| (rng.gen_range(0.0, 100.0), rng.gen_range(0.0, 100.0)))
+            .collect();
+        let mut g = Graph::new(kind);
+        for i in 0..n {
+            g.add_node(i, ()).unwrap();
+        }
+        let mut e = 0;
+        for i in 0..n {
+            for j in 0..n {
+                if i == j || (kind == GraphKind::Undirected && j < i) {
+                    continue;
+                }
+                let (dx, dy) = (pts[i].0 - pts[j].0, pts[i].1 - pts[j].1);
+                //one way costs a bit more uphill
+                let up = if kind == GraphKind::Directed && j > i {
+                    7
+                } else {
+                    0
+                };
+                g.add_edge(e, i, j, (dx * dx + dy * dy).sqrt() as i32 + up)
+                    .unwrap();
+                e += 1;
+            }
+        }
+        g
+    }
+
+    fn brute(tsp: &Tsp<usize>, n: usize) -> i32 {
+        fn perms(rest: &mut Vec<usize>, k: usize, f: &mut dyn FnMut(&[usize])) {
+            if k == rest.len() {
+                return f(rest);
+            }
+            for i in k..rest.len() {
+                rest.swap(k, i);
+                perms(rest, k + 1, f);
+                rest.swap(k, i);
+            }
+        }
+        let mut best = i32::MAX;
+        let mut rest: Vec<usize> = (0..n).collect();
+        perms(&mut rest, 1, &mut |p| best = best.min(tsp.cost(p)));
+        best
+    }
+
+    #[test]
+    fn test_held_karp_exact() {
+        for kind in &[GraphKind::Undirected, GraphKind::Directed] {
+            let g = cities(8, 3, *kind);
+            let tsp = g.tsp().unwrap();
+            let exact = tsp.held_karp(&0).unwrap();
+            let t: Vec<usize> = tsp.indices(&exact);
+            assert_eq!(tsp.cost(&t), exact.len);
+            assert_eq!(exact.len, brute(&tsp, 8));
+            assert_eq!(exact.order[0], 0);
+            let mut o = exact.order.clone();
+            o.sort();
+            assert_eq!(o, (0..8).collect::<Vec<_>>());
+        }
+        let g = cities(MAX_EXACT + 1, 1, GraphKind::Undirected);
+        assert!(g.tsp().unwrap().held_karp(&0).is_none());
+    }
+
+    #[test]
+    fn test_heuristics_near_optimum() {
+        for seed in 0..4 {
+            let g = cities(12, seed, GraphKind::Undirected);
+            let tsp = g.tsp().unwrap();
+            let opt = tsp.held_karp(&0).unwrap().len;
+            let nn = tsp.nearest_neighbour(&0).unwrap();
+            let two = tsp.two_opt(&nn);
+            let or = tsp.or_opt(&nn);
+            let both = tsp.local_search(&nn);
+            let sa = tsp.anneal(&nn, &Anneal::default());
+            for t in &[&nn, &two, &or, &both, &sa] {
+                assert!(t.len >= opt);
+                assert_eq!(t.order[0], 0);
+            }
+            assert!(two.len <= nn.len && or.len <= nn.len && both.len <= two.len.max(or.len));
+            // local search should be within a tenth, annealing close to exact
+            assert!(both.len * 10 <= opt * 11, "{} vs {}", both.len, opt);
+            assert!(sa.len * 20 <= opt * 21, "{} vs {}", sa.len, opt);
+            assert_eq!(sa, tsp.anneal(&nn, &Anneal::default()));
+        }
+    }
+
+    #[test]
+    fn test_one_way_costs() {
+        let g = cities(9, 5, GraphKind::Directed);
+        let tsp = g.tsp().unwrap();
+        let opt = tsp.held_karp(&0).unwrap().len;
+        let nn = tsp.nearest_neighbour(&0).unwrap();
+        let best = tsp.local_search(&nn);
+        assert_eq!(best.len, tsp.cost(&tsp.indices(&best)));
+        assert!(best.len >= opt && best.len <= nn.len);
+    }
+}
@@ -56,6 +56,18 @@ impl Blob {
         Ok(bincode::deserialize(&self.v[..])?)
     }
 
+    pub fn get_k<'a, K: Deserialize<'a>>(&'a self) -> Result<K, BlobError> {
+        Ok(bincode::deserialize(&self.k[..])?)
+    }
+
+    /// Same key with no value, for index entries that only need the key
+    pub fn key_only(&self) -> Blob {
+        Blob {
+            k: self.k.clone(),
+            v: Vec::new(),
+        }
+    }
+
     pub fn len(&self) -> u64 {
         (16 + self.k.len() + self.v.len()) as u64
     }
@@ -0,0 +1,485 @@
+//! In memory B-tree, the same layout a file based store wants:
+//! wide nodes holding up to B-1 sorted keys, so few nodes are visited per lookup.
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Debug)]
+pub struct BTreeMap<K, V, const B: usize> {
+    root: Node<K, V, B>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<K, V, const B: usize> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    //empty for leaves, otherwise always keys.len()+1
+    children: Vec<Node<K, V, B>>,
+}
+
+impl<K, V, const B: usize> Node<K, V, B> {
+    /// Every node but the root keeps at least this many keys
+    const MIN: usize = (B - 1) / 2;
+
+    fn new() -> Self {
+        Node {
+            keys: Vec::with_capacity(B),
+            vals: Vec::with_capacity(B),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Split a node that has reached B keys,
+    /// returns the middle entry to move up, and the new right half
+    fn split(&mut self) -> (K, V, Self) {
+        let mid = self.keys.len() / 2;
+        let keys = self.keys.split_off(mid + 1);
+        let vals = self.vals.split_off(mid + 1);
+        let children = match self.is_leaf() {
+            true => Vec::new(),
+            false => self.children.split_off(mid + 1),
+        };
+        //mid + 1 <= len so there is always a middle left
+        let k = self.keys.pop().unwrap();
+        let v = self.vals.pop().unwrap();
+        (
+            k,
+            v,
+            Node {
+                keys,
+                vals,
+                children,
+            },
+        )
+    }
+
+    /// Remove the largest entry, keeping every child on the way full enough
+    fn pop_last(&mut self) -> (K, V) {
+        if self.is_leaf() {
+            //only called on non empty subtrees
+            return (self.keys.pop().unwrap(), self.vals.pop().unwrap());
+        }
+        let i = self.children.len() - 1;
+        let res = self.children[i].pop_last();
+        self.fix_child(i);
+        res
+    }
+
+    /// After a remove child i may be short by one key,
+    /// take one from a sibling through the parent, or if they are both minimal merge.
+    fn fix_child(&mut self, i: usize) {
+        if self.children[i].keys.len() >= Self::MIN {
+            return;
+        }
+        if i > 0 && self.children[i - 1].keys.len() > Self::MIN {
+            let (l, r) = self.children.split_at_mut(i);
+            let (left, child) = (&mut l[i - 1], &mut r[0]);
+            let k = std::mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+            let v = std::mem::replace(&mut self.vals[i - 1], left.vals.pop().unwrap());
+            child.keys.insert(0, k);
+            child.vals.insert(0, v);
+            if let Some(c) = left.children.pop() {
+                child.children.insert(0, c);
+            }
+            return;
+        }
+        if i + 1 < self.children.len() && self.children[i + 1].keys.len() > Self::MIN {
+            let (l, r) = self.children.split_at_mut(i + 1);
+            let (child, right) = (&mut l[i], &mut r[0]);
+            let k = std::mem::replace(&mut self.keys[i], right.keys.remove(0));
+            let v = std::mem::replace(&mut self.vals[i], right.vals.remove(0));
+            child.keys.push(k);
+            child.vals.push(v);
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+            return;
+        }
+        match i {
+            0 => self.merge(0),
+            _ => self.merge(i - 1),
+        }
+    }
+
+    /// Join child i+1 onto child i, with the key between them in the middle
+    fn merge(&mut self, i: usize) {
+        let right = self.children.remove(i + 1);
+        let k = self.keys.remove(i);
+        let v = self.vals.remove(i);
+        let left = &mut self.children[i];
+        left.keys.push(k);
+        left.vals.push(v);
+        left.keys.extend(right.keys);
+        left.vals.extend(right.vals);
+        left.children.extend(right.children);
+    }
+
+    /// Build a subtree of height h from the next n sorted items
+    fn build<I: Iterator<Item = (K, V)>>(it: &mut I, n: usize, h: u32) -> Self {
+        let mut res = Node::new();
+        if h <= 1 {
+            for (k, v) in it.take(n) {
+                res.keys.push(k);
+                res.vals.push(v);
+            }
+            return res;
+        }
+        // Fewest children that can hold n, then share the items evenly
+        // so every child ends up at least half full
+        let sub = capacity::<B>(h - 1);
+        let nch = (n + 1 + sub) / (sub + 1);
+        let in_children = n - (nch - 1);
+        for c in 0..nch {
+            let cn = in_children / nch + usize::from(c < in_children % nch);
+            res.children.push(Node::build(it, cn, h - 1));
+            if c + 1 < nch {
+                //counted so there are enough items
+                let (k, v) = it.next().unwrap();
+                res.keys.push(k);
+                res.vals.push(v);
+            }
+        }
+        res
+    }
+}
+
+/// Most keys a tree of height h can hold
+fn capacity<const B: usize>(h: u32) -> usize {
+    B.saturating_pow(h) - 1
+}
+
+impl<K: Ord, V, const B: usize> Node<K, V, B> {
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let i = match self.keys.binary_search(&k) {
+            Ok(i) => return Some(std::mem::replace(&mut self.vals[i], v)),
+            Err(i) => i,
+        };
+        if self.is_leaf() {
+            self.keys.insert(i, k);
+            self.vals.insert(i, v);
+            return None;
+        }
+        let res = self.children[i].insert(k, v);
+        if self.children[i].keys.len() >= B {
+            let (mk, mv, right) = self.children[i].split();
+            self.keys.insert(i, mk);
+            self.vals.insert(i, mv);
+            self.children.insert(i + 1, right);
+        }
+        res
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        match self.keys.binary_search(k) {
+            Ok(i) if self.is_leaf() => {
+                self.keys.remove(i);
+                Some(self.vals.remove(i))
+            }
+            Ok(i) => {
+                // Replace with the largest key before it, which is always in a leaf
+                let (pk, pv) = self.children[i].pop_last();
+                self.keys[i] = pk;
+                let res = std::mem::replace(&mut self.vals[i], pv);
+                self.fix_child(i);
+                Some(res)
+            }
+            Err(_) if self.is_leaf() => None,
+            Err(i) => {
+                let res = self.children[i].remove(k)?;
+                self.fix_child(i);
+                Some(res)
+            }
+        }
+    }
+}
+
+impl<K, V, const B: usize> Default for BTreeMap<K, V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const B: usize> BTreeMap<K, V, B> {
+    pub fn new() -> Self {
+        assert!(B >= 3, "BTreeMap needs room for at least 2 keys per node");
+        BTreeMap {
+            root: Node::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Range<'_, K, V, (Bound<K>, Bound<K>), B>
+    where
+        K: Ord,
+    {
+        self.range((Bound::Unbounded, Bound::Unbounded))
+    }
+}
+
+impl<K: Ord, V, const B: usize> BTreeMap<K, V, B> {
+    /// Build directly from items in strictly increasing key order,
+    /// O(n) rather than n separate inserts. Panics if the input is not sorted.
+    pub fn from_sorted<I: IntoIterator<Item = (K, V)>>(items: I) -> Self {
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        assert!(
+            items.windows(2).all(|w| w[0].0 < w[1].0),
+            "from_sorted needs strictly increasing keys"
+        );
+        let len = items.len();
+        let mut h = 1;
+        while capacity::<B>(h) < len {
+            h += 1;
+        }
+        let mut res = Self::new();
+        res.root = Node::build(&mut items.into_iter(), len, h);
+        res.len = len;
+        res
+    }
+
+    pub fn get(&self, k: &K) -> Option<&V> {
+        let mut node = &self.root;
+        loop {
+            match node.keys.binary_search(k) {
+                Ok(i) => return Some(&node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = &node.children[i],
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        loop {
+            match node.keys.binary_search(k) {
+                Ok(i) => return Some(&mut node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = &mut node.children[i],
+            }
+        }
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let res = self.root.insert(k, v);
+        if self.root.keys.len() >= B {
+            // Grow from the top, so all leaves stay at the same depth
+            let mut left = std::mem::replace(&mut self.root, Node::new());
+            let (k, v, right) = left.split();
+            self.root.keys.push(k);
+            self.root.vals.push(v);
+            self.root.children = vec![left, right];
+        }
+        if res.is_none() {
+            self.len += 1;
+        }
+        res
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let res = self.root.remove(k)?;
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            //Only child becomes the root, shrinking the tree by one level
+            self.root = self.root.children.pop().unwrap();
+        }
+        self.len -= 1;
+        Some(res)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, r: R) -> Range<'_, K, V, R, B> {
+        let mut res = Range {
+            stack: Vec::new(),
+            bounds: r,
+        };
+        // Walk down to the first key in range, remembering where we are in each node
+        let mut node = &self.root;
+        loop {
+            let i = match res.bounds.start_bound() {
+                Bound::Included(lo) => node.keys.partition_point(|k| k < lo),
+                Bound::Excluded(lo) => node.keys.partition_point(|k| k <= lo),
+                Bound::Unbounded => 0,
+            };
+            res.stack.push((node, i));
+            if node.is_leaf() {
+                return res;
+            }
+            node = &node.children[i];
+        }
+    }
+}
+
+/// Ordered iterator, each stack entry is a node and the next key to hand out from it
+pub struct Range<'a, K, V, R, const B: usize> {
+    stack: Vec<(&'a Node<K, V, B>, usize)>,
+    bounds: R,
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>, const B: usize> Iterator for Range<'a, K, V, R, B> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let node: &'a Node<K, V, B> = node;
+            if *i >= node.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+            let at = *i;
+            *i += 1;
+            let in_range = match self.bounds.end_bound() {
+                Bound::Included(hi) => node.keys[at] <= *hi,
+                Bound::Excluded(hi) => node.keys[at] < *hi,
+                Bound::Unbounded => true,
+            };
+            if !in_range {
+                self.stack.clear();
+                return None;
+            }
+            // Everything in the child after this key comes next
+            if !node.is_leaf() {
+                let mut ch = &node.children[at + 1];
+                loop {
+                    self.stack.push((ch, 0));
+                    if ch.is_leaf() {
+                        break;
+                    }
+                    ch = &ch.children[0];
+                }
+            }
+            return Some((&node.keys[at], &node.vals[at]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap as StdMap;
+
+    /// Returns the depth, so we can check all leaves are level
+    fn check_node<K: Ord, V, const B: usize>(n: &Node<K, V, B>, root: bool) -> usize {
+        assert!(n.keys.len() < B);
+        assert!(root || n.keys.len() >= Node::<K, V, B>::MIN);
+        assert_eq!(n.keys.len(), n.vals.len());
+        assert!(n.keys.windows(2).all(|w| w[0] < w[1]));
+        if n.is_leaf() {
+            return 1;
+        }
+        assert_eq!(n.children.len(), n.keys.len() + 1);
+        let depth = check_node(&n.children[0], false);
+        for c in &n.children[1..] {
+            assert_eq!(check_node(c, false), depth);
+        }
+        depth + 1
+    }
+
+    fn check<K: Ord, V, const B: usize>(t: &BTreeMap<K, V, B>) {
+        check_node(&t.root, true);
+        assert_eq!(t.iter().count(), t.len());
+    }
+
+    fn same_as_std<const B: usize>(seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut t: BTreeMap<u32, u32, B> = BTreeMap::new();
+        let mut model = StdMap::new();
+        for step in 0..4000 {
+            let k = rng.gen_range(0, 500);
+            match rng.gen_range(0, 3) {
+                0 | 1 => {
+                    let v = rng.gen();
+                    assert_eq!(t.insert(k, v), model.insert(k, v));
+                }
+                _ => assert_eq!(t.remove(&k), model.remove(&k)),
+            }
+            assert_eq!(t.get(&k), model.get(&k));
+            if step % 100 == 0 {
+                check(&t);
+            }
+        }
+        check(&t);
+        assert!(t.iter().eq(model.iter()));
+        for _ in 0..50 {
+            let a = rng.gen_range(0, 520);
+            let b = rng.gen_range(a, 520);
+            assert!(t.range(a..b).eq(model.range(a..b)));
+            assert!(t.range(a..=b).eq(model.range(a..=b)));
+            assert!(t.range(a..).eq(model.range(a..)));
+            assert!(t
+                .range((Bound::Excluded(a), Bound::Unbounded))
+                .eq(model.range((Bound::Excluded(a), Bound::Unbounded))));
+        }
+        let keys: Vec<u32> = model.keys().cloned().collect();
+        for k in keys {
+            assert_eq!(t.remove(&k), model.remove(&k));
+        }
+        check(&t);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_matches_std_btreemap() {
+        for seed in 0..5 {
+            same_as_std::<3>(seed);
+            same_as_std::<4>(seed);
+            same_as_std::<5>(seed);
+            same_as_std::<16>(seed);
+        }
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        for n in 0..300u32 {
+            let t3: BTreeMap<u32, u32, 3> = BTreeMap::from_sorted((0..n).map(|i| (i, i * 2)));
+            let t4: BTreeMap<u32, u32, 4> = BTreeMap::from_sorted((0..n).map(|i| (i, i * 2)));
+            let t7: BTreeMap<u32, u32, 7> = BTreeMap::from_sorted((0..n).map(|i| (i, i * 2)));
+            check(&t3);
+            check(&t4);
+            check(&t7);
+            assert!(t7
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .eq((0..n).map(|i| (i, i * 2))));
+            assert_eq!(t4.get(&(n / 2)).is_some(), n > 0);
+        }
+        //Bulk loaded trees must still take changes
+        let mut t: BTreeMap<u32, u32, 5> = BTreeMap::from_sorted((0..100).map(|i| (i * 2, i)));
+        for i in 0..200 {
+            t.insert(i, i);
+            check(&t);
+        }
+        for i in (0..200).step_by(3) {
+            t.remove(&i);
+            check(&t);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bulk_load_unsorted() {
+        let _: BTreeMap<i32, i32, 4> = BTreeMap::from_sorted(vec![(2, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut t: BTreeMap<&str, i32, 4> = BTreeMap::new();
+        t.insert("a", 1);
+        *t.get_mut(&"a").unwrap() += 5;
+        assert_eq!(t.get(&"a"), Some(&6));
+        assert!(t.contains_key(&"a"));
+        assert!(!t.contains_key(&"b"));
+    }
+}
@@ -0,0 +1,524 @@
+use crate::error::BlobError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds, RangeFull};
+
+use crate::blob::{read_u64, write_u64, Blob};
+
+//Every page starts with kind, count and next
+const PAGE_HEAD: u64 = 24;
+//room for the head and 4 u64 records (16 blob head, 8 key, 8 value, 8 child pointer),
+//also keeps page 1 clear of the 32 byte store header
+const MIN_PAGE: u64 = PAGE_HEAD + 4 * 40;
+const LEAF: u64 = 1;
+const INTERNAL: u64 = 2;
+//page numbers start at 1, page 0 holds the store header
+const NO_PAGE: u64 = 0;
+
+/// A B+ tree in a file. All records live in leaf pages, in key order,
+/// and each leaf points to the next so ranges can be read without going back up.
+/// Unlike BlobStore keys are compared by value, so K must be the same type throughout.
+pub struct BTreeStore<K> {
+    file: File,
+    page_size: u64,
+    root: u64,
+    npages: u64,
+    elems: u64,
+    k: PhantomData<K>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Page {
+    Leaf { recs: Vec<Blob>, next: u64 },
+    // children.len() == keys.len() + 1, child i holds keys below keys[i]
+    Internal { keys: Vec<Blob>, children: Vec<u64> },
+}
+
+impl Page {
+    fn size(&self) -> u64 {
+        match self {
+            Page::Leaf { recs, .. } => PAGE_HEAD + recs.iter().map(|b| b.len()).sum::<u64>(),
+            Page::Internal { keys, children } => {
+                PAGE_HEAD + 8 * children.len() as u64 + keys.iter().map(|b| b.len()).sum::<u64>()
+            }
+        }
+    }
+}
+
+/// Index to split at so both halves hold about the same number of bytes
+fn split_point(blobs: &[Blob]) -> usize {
+    let total: u64 = blobs.iter().map(|b| b.len()).sum();
+    let mut sofar = 0;
+    for (i, b) in blobs.iter().enumerate() {
+        sofar += b.len();
+        if sofar * 2 >= total {
+            return std::cmp::max(i, 1);
+        }
+    }
+    blobs.len() / 2
+}
+
+fn read_keys<K: DeserializeOwned>(blobs: &[Blob]) -> Result<Vec<K>, BlobError> {
+    blobs.iter().map(|b| b.get_k()).collect()
+}
+
+fn check_page_size(page_size: u64) -> anyhow::Result<()> {
+    if page_size < MIN_PAGE {
+        anyhow::bail!("page size {} below minimum {}", page_size, MIN_PAGE);
+    }
+    Ok(())
+}
+
+impl<K: Serialize + DeserializeOwned + Ord> BTreeStore<K> {
+    pub fn new(fname: &str, page_size: u64) -> anyhow::Result<Self> {
+        check_page_size(page_size)?;
+        let ff = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .read(true)
+            .open(fname)?;
+        let mut res = BTreeStore {
+            file: ff,
+            page_size,
+            root: 1,
+            npages: 1,
+            elems: 0,
+            k: PhantomData,
+        };
+        let root = res.alloc_page()?;
+        res.write_page(
+            root,
+            &Page::Leaf {
+                recs: Vec::new(),
+                next: NO_PAGE,
+            },
+        )?;
+        res.write_head()?;
+        Ok(res)
+    }
+
+    pub fn open(fname: &str) -> anyhow::Result<Self> {
+        let mut ff = OpenOptions::new().write(true).read(true).open(fname)?;
+        ff.seek(SeekFrom::Start(0))?;
+        let f = &mut ff;
+        let page_size = read_u64(f)?;
+        let root = read_u64(f)?;
+        let npages = read_u64(f)?;
+        let elems = read_u64(f)?;
+        check_page_size(page_size)?;
+        Ok(BTreeStore {
+            file: ff,
+            page_size,
+            root,
+            npages,
+            elems,
+            k: PhantomData,
+        })
+    }
+
+    pub fn new_or_open(fname: &str, page_size: u64) -> anyhow::Result<Self> {
+        check_page_size(page_size)?;
+        Self::new(fname, page_size).or_else(|_| Self::open(fname))
+    }
+
+    pub fn n_elems(&self) -> u64 {
+        self.elems
+    }
+
+    fn write_head(&mut self) -> Result<(), BlobError> {
+        let f = &mut self.file;
+        f.seek(SeekFrom::Start(0))?;
+        write_u64(f, self.page_size)?;
+        write_u64(f, self.root)?;
+        write_u64(f, self.npages)?;
+        write_u64(f, self.elems)?;
+        Ok(())
+    }
+
+    /// Pages are never freed, removes leave the space for later inserts to the same leaf
+    fn alloc_page(&mut self) -> Result<u64, BlobError> {
+        let res = self.npages;
+        self.npages += 1;
+        self.file.set_len(self.npages * self.page_size)?;
+        Ok(res)
+    }
+
+    fn read_page(&mut self, pno: u64) -> Result<Page, BlobError> {
+        if pno == NO_PAGE || pno >= self.npages {
+            return Err(BlobError::BadPage(pno));
+        }
+        let f = &mut self.file;
+        f.seek(SeekFrom::Start(pno * self.page_size))?;
+        let kind = read_u64(f)?;
+        let count = read_u64(f)?;
+        let next = read_u64(f)?;
+        match kind {
+            LEAF => {
+                let mut recs = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    recs.push(Blob::read(f)?);
+                }
+                Ok(Page::Leaf { recs, next })
+            }
+            INTERNAL => {
+                let mut children = Vec::with_capacity(count as usize + 1);
+                for _ in 0..=count {
+                    children.push(read_u64(f)?);
+                }
+                let mut keys = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    keys.push(Blob::read(f)?);
+                }
+                Ok(Page::Internal { keys, children })
+            }
+            _ => Err(BlobError::BadPage(pno)),
+        }
+    }
+
+    fn write_page(&mut self, pno: u64, page: &Page) -> Result<(), BlobError> {
+        if page.size() > self.page_size {
+            return Err(BlobError::NoRoom);
+        }
+        let f = &mut self.file;
+        f.seek(SeekFrom::Start(pno * self.page_size))?;
+        match page {
+            Page::Leaf { recs, next } => {
+                write_u64(f, LEAF)?;
+                write_u64(f, recs.len() as u64)?;
+                write_u64(f, *next)?;
+                for b in recs {
+                    b.out(f)?;
+                }
+            }
+            Page::Internal { keys, children } => {
+                write_u64(f, INTERNAL)?;
+                write_u64(f, keys.len() as u64)?;
+                write_u64(f, NO_PAGE)?;
+                for c in children {
+                    write_u64(f, *c)?;
+                }
+                for b in keys {
+                    b.out(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts or replaces the value for k
+    pub fn insert<V: Serialize>(&mut self, k: &K, v: V) -> Result<(), BlobError> {
+        let blob = Blob::from(k, &v)?;
+        // A quarter page each means a split always leaves two halves that fit
+        if (blob.len() + 8) * 4 > self.page_size - PAGE_HEAD {
+            return Err(BlobError::TooBig(blob.len()));
+        }
+        let root = self.root;
+        if let Some((sep, right)) = self.insert_at(root, k, blob)? {
+            //Root split, grow a level above it
+            let nroot = self.alloc_page()?;
+            let page = Page::Internal {
+                keys: vec![sep],
+                children: vec![root, right],
+            };
+            self.write_page(nroot, &page)?;
+            self.root = nroot;
+        }
+        self.write_head()
+    }
+
+    /// If the page had to split, returns the first key of the new right page and its number
+    fn insert_at(&mut self, pno: u64, k: &K, blob: Blob) -> Result<Option<(Blob, u64)>, BlobError> {
+        match self.read_page(pno)? {
+            Page::Leaf { mut recs, next } => {
+                match read_keys::<K>(&recs)?.binary_search(k) {
+                    Ok(i) => recs[i] = blob,
+                    Err(i) => {
+                        recs.insert(i, blob);
+                        self.elems += 1;
+                    }
+                }
+                let page = Page::Leaf { recs, next };
+                if page.size() <= self.page_size {
+                    self.write_page(pno, &page)?;
+                    return Ok(None);
+                }
+                let (mut recs, next) = match page {
+                    Page::Leaf { recs, next } => (recs, next),
+                    _ => unreachable!(),
+                };
+                let rrecs = recs.split_off(split_point(&recs));
+                let sep = rrecs[0].key_only();
+                let rpno = self.alloc_page()?;
+                self.write_page(rpno, &Page::Leaf { recs: rrecs, next })?;
+                self.write_page(pno, &Page::Leaf { recs, next: rpno })?;
+                Ok(Some((sep, rpno)))
+            }
+            Page::Internal {
+                mut keys,
+                mut children,
+            } => {
+                let i = read_keys::<K>(&keys)?.partition_point(|x| x <= k);
+                let (sep, rchild) = match self.insert_at(children[i], k, blob)? {
+                    Some(s) => s,
+                    None => return Ok(None),
+                };
+                keys.insert(i, sep);
+                children.insert(i + 1, rchild);
+                let page = Page::Internal { keys, children };
+                if page.size() <= self.page_size {
+                    self.write_page(pno, &page)?;
+                    return Ok(None);
+                }
+                let (mut keys, mut children) = match page {
+                    Page::Internal { keys, children } => (keys, children),
+                    _ => unreachable!(),
+                };
+                // Unlike a leaf the middle key moves up rather than being copied
+                let mid = split_point(&keys);
+                let rkeys = keys.split_off(mid + 1);
+                let rchildren = children.split_off(mid + 1);
+                let up = keys.pop().unwrap(); //mid + 1 <= len
+                let rpno = self.alloc_page()?;
+                self.write_page(
+                    rpno,
+                    &Page::Internal {
+                        keys: rkeys,
+                        children: rchildren,
+                    },
+                )?;
+                self.write_page(pno, &Page::Internal { keys, children })?;
+                Ok(Some((up, rpno)))
+            }
+        }
+    }
+
+    /// Follow the index down to the leaf that would hold k
+    fn find_leaf(&mut self, k: Bound<&K>) -> Result<(Vec<Blob>, u64), BlobError> {
+        let mut pno = self.root;
+        loop {
+            match self.read_page(pno)? {
+                Page::Leaf { recs, next } => return Ok((recs, next)),
+                Page::Internal { keys, children } => {
+                    let i = match k {
+                        Bound::Included(k) | Bound::Excluded(k) => {
+                            read_keys::<K>(&keys)?.partition_point(|x| x <= k)
+                        }
+                        Bound::Unbounded => 0,
+                    };
+                    pno = children[i];
+                }
+            }
+        }
+    }
+
+    pub fn get(&mut self, k: &K) -> Result<Blob, BlobError> {
+        let (recs, _) = self.find_leaf(Bound::Included(k))?;
+        match read_keys::<K>(&recs)?.binary_search(k) {
+            Ok(i) => Ok(recs.into_iter().nth(i).unwrap()),
+            Err(_) => Err(BlobError::NotFound),
+        }
+    }
+
+    /// Leaves are not merged, an emptied leaf just stays in the chain
+    pub fn remove(&mut self, k: &K) -> Result<(), BlobError> {
+        let mut pno = self.root;
+        loop {
+            match self.read_page(pno)? {
+                Page::Leaf { mut recs, next } => {
+                    if let Ok(i) = read_keys::<K>(&recs)?.binary_search(k) {
+                        recs.remove(i);
+                        self.write_page(pno, &Page::Leaf { recs, next })?;
+                        self.elems -= 1;
+                        self.write_head()?;
+                    }
+                    return Ok(());
+                }
+                Page::Internal { keys, children } => {
+                    pno = children[read_keys::<K>(&keys)?.partition_point(|x| x <= k)];
+                }
+            }
+        }
+    }
+
+    /// Ordered cursor over the records with keys in r
+    pub fn range<R: RangeBounds<K>>(&mut self, r: R) -> Result<Cursor<'_, K, R>, BlobError> {
+        let (recs, next) = self.find_leaf(r.start_bound())?;
+        Ok(Cursor {
+            store: self,
+            recs: recs.into_iter(),
+            next,
+            bounds: r,
+        })
+    }
+
+    /// Ordered cursor over every record
+    pub fn cursor(&mut self) -> Result<Cursor<'_, K, RangeFull>, BlobError> {
+        self.range(..)
+    }
+}
+
+/// Reads one leaf at a time, following the next links between leaves
+pub struct Cursor<'a, K, R> {
+    store: &'a mut BTreeStore<K>,
+    recs: std::vec::IntoIter<Blob>,
+    next: u64,
+    bounds: R,
+}
+
+impl<'a, K: Serialize + DeserializeOwned + Ord, R: RangeBounds<K>> Iterator for Cursor<'a, K, R> {
+    type Item = Result<Blob, BlobError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let b = match self.recs.next() {
+                Some(b) => b,
+                None => {
+                    if self.next == NO_PAGE {
+                        return None;
+                    }
+                    match self.store.read_page(self.next) {
+                        Ok(Page::Leaf { recs, next }) => {
+                            self.recs = recs.into_iter();
+                            self.next = next;
+                            continue;
+                        }
+                        Ok(_) => {
+                            let bad = self.next;
+                            self.next = NO_PAGE;
+                            return Some(Err(BlobError::BadPage(bad)));
+                        }
+                        Err(e) => {
+                            self.next = NO_PAGE;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            };
+            let k: K = match b.get_k() {
+                Ok(k) => k,
+                Err(e) => return Some(Err(e)),
+            };
+            //only the first leaf can hold keys before the start
+            if !self.bounds.contains(&k) {
+                let before_start = match self.bounds.start_bound() {
+                    Bound::Included(s) => k < *s,
+                    Bound::Excluded(s) => k <= *s,
+                    Bound::Unbounded => false,
+                };
+                if before_start {
+                    continue;
+                }
+                self.recs = Vec::new().into_iter();
+                self.next = NO_PAGE;
+                return None;
+            }
+            return Some(Ok(b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh path under the temp dir, so test runs leave the tree clean
+    fn tmp(name: &str) -> String {
+        let p = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::remove_file(&p).ok();
+        p.to_string_lossy().into_owned()
+    }
+
+    fn keys_of<R: RangeBounds<u64>>(bs: &mut BTreeStore<u64>, r: R) -> Vec<u64> {
+        bs.range(r)
+            .unwrap()
+            .map(|b| b.unwrap().get_k().unwrap())
+            .collect()
+    }
+
+    #[test]
+    pub fn test_insert_get_range() {
+        let f = tmp("bt_1");
+        let mut bs = BTreeStore::new(&f, 256).unwrap();
+        // Insert out of order so splits happen all over the tree
+        for i in 0..500u64 {
+            let k = (i * 7919) % 500;
+            bs.insert(&k, format!("value {}", k)).unwrap();
+        }
+        assert_eq!(bs.n_elems(), 500);
+        assert!(bs.npages > 10);
+        assert_eq!(
+            bs.get(&321).unwrap().get_v::<String>().unwrap(),
+            "value 321".to_string()
+        );
+        assert!(bs.get(&600).is_err());
+
+        assert_eq!(keys_of(&mut bs, 10..15), vec![10, 11, 12, 13, 14]);
+        assert_eq!(keys_of(&mut bs, 495..), vec![495, 496, 497, 498, 499]);
+        assert_eq!(keys_of(&mut bs, ..), (0..500).collect::<Vec<u64>>());
+
+        //replace keeps the count
+        bs.insert(&5, "five").unwrap();
+        assert_eq!(bs.get(&5).unwrap().get_v::<String>().unwrap(), "five");
+        assert_eq!(bs.n_elems(), 500);
+
+        for i in (0..500).step_by(2) {
+            bs.remove(&i).unwrap();
+        }
+        assert_eq!(bs.n_elems(), 250);
+        assert!(bs.get(&4).is_err());
+        assert_eq!(keys_of(&mut bs, 10..=15), vec![11, 13, 15]);
+    }
+
+    #[test]
+    pub fn test_reopen_keeps_tree() {
+        let f = tmp("bt_reopen");
+        {
+            let mut bs = BTreeStore::new(&f, 256).unwrap();
+            for i in 0..300u64 {
+                bs.insert(&i, i * 3).unwrap();
+            }
+        }
+        let mut b2: BTreeStore<u64> = BTreeStore::open(&f).unwrap();
+        assert_eq!(b2.n_elems(), 300);
+        assert_ne!(b2.root, 1, "root should have split");
+        assert_eq!(b2.get(&77).unwrap().get_v::<u64>().unwrap(), 231);
+        assert_eq!(keys_of(&mut b2, 100..103), vec![100, 101, 102]);
+        b2.insert(&1000, 1).unwrap();
+        assert_eq!(b2.cursor().unwrap().count(), 301);
+    }
+
+    #[test]
+    pub fn test_string_keys_and_too_big() {
+        let f = tmp("bt_str");
+        let mut bs = BTreeStore::new(&f, 512).unwrap();
+        for w in &["pear", "apple", "fig", "banana", "cherry"] {
+            bs.insert(&w.to_string(), w.len()).unwrap();
+        }
+        let words: Vec<String> = bs
+            .range("b".to_string().."d".to_string())
+            .unwrap()
+            .map(|b| b.unwrap().get_k().unwrap())
+            .collect();
+        assert_eq!(words, vec!["banana".to_string(), "cherry".to_string()]);
+        let big = "x".repeat(400);
+        assert!(bs.insert(&big, 0).is_err());
+    }
+
+    #[test]
+    pub fn test_page_size_too_small() {
+        let f = tmp("bt_small");
+        for ps in &[0, 20, 31, MIN_PAGE - 1] {
+            assert!(BTreeStore::<u64>::new(&f, *ps).is_err());
+            assert!(BTreeStore::<u64>::new_or_open(&f, *ps).is_err());
+        }
+        // nothing was created by the failed tries
+        assert!(std::fs::metadata(&f).is_err());
+        let mut bs = BTreeStore::new(&f, MIN_PAGE).unwrap();
+        for i in 0..50u64 {
+            bs.insert(&i, i).unwrap();
+        }
+        assert_eq!(bs.get(&42).unwrap().get_v::<u64>().unwrap(), 42);
+    }
+}
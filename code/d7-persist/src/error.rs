@@ -8,6 +8,8 @@ pub enum BlobError {
     TooBig(u64),
     #[error("Item Not Fount")]
     NotFound,
+    #[error("Bad Page {0}")]
+    BadPage(u64),
     #[error("{}", 0)]
     Bincode(bincode::Error),
     #[error("{}", 0)]
@@ -3,6 +3,8 @@
 
 pub mod blob;
 pub mod blobstore;
+pub mod btree;
+pub mod btreestore;
 pub mod error;
 
 //extern crate serde_derive;